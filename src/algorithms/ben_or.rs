@@ -0,0 +1,283 @@
+use std::{collections::BTreeMap, time::Duration};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use crate::{Node, NodeEvent, Effect, MessageSize, MessageKind, Decisions};
+
+#[derive(Clone, Hash)]
+pub struct BenOrConfig {
+    pub id: usize,
+    pub total_nodes: usize,
+    pub faulty: usize,
+    pub initial: bool,
+    pub seed: u64,
+}
+
+impl BenOrConfig {
+    pub fn new(
+        total_nodes: usize,
+        faulty: usize,
+        seed: u64,
+    ) -> impl Iterator<Item = Self> {
+        assert!(total_nodes > 2 * faulty, "Ben-Or requires n > 2f");
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..total_nodes)
+            .map(move |id| BenOrConfig {
+                id,
+                total_nodes,
+                faulty,
+                initial: rng.gen(),
+                seed: rng.gen(),
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Report,
+    Proposal,
+}
+
+#[derive(Debug, Clone, Hash)]
+pub struct BenOrMessage {
+    sender: usize,
+    round: u32,
+    phase: Phase,
+    // in the proposal phase `None` stands for `?`
+    value: Option<bool>,
+}
+
+impl MessageSize for BenOrMessage {
+    fn size(&self) -> usize {
+        // sender and round are 32 bit, phase and value take a byte each
+        4 + 4 + 1 + 1
+    }
+}
+
+impl MessageKind for BenOrMessage {
+    type Proposal = bool;
+
+    fn kind(&self) -> &'static str {
+        match self.phase {
+            Phase::Report => "report",
+            Phase::Proposal => "proposal",
+        }
+    }
+
+    fn proposal(&self) -> Option<(Self::Proposal, u64)> {
+        match (self.phase, self.value) {
+            (Phase::Proposal, Some(value)) => Some((value, u64::from(self.round) - 1)),
+            _ => None,
+        }
+    }
+}
+
+type Votes = Vec<(usize, Option<bool>)>;
+
+#[derive(Clone, Hash)]
+pub struct BenOrNode {
+    config: BenOrConfig,
+    value: bool,
+    round: u32,
+    phase: Phase,
+    received: BTreeMap<(u32, Phase), Votes>,
+    decision: Option<(bool, u32)>,
+    halted: bool,
+}
+
+impl BenOrNode {
+    pub fn new(config: BenOrConfig) -> Self {
+        BenOrNode {
+            value: config.initial,
+            config,
+            round: 0,
+            phase: Phase::Report,
+            received: BTreeMap::new(),
+            decision: None,
+            halted: false,
+        }
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    // the decided value and the round in which the decision was made
+    pub fn decided(&self) -> Option<(bool, u32)> {
+        self.decision
+    }
+
+    fn record(&mut self, sender: usize, round: u32, phase: Phase, value: Option<bool>) {
+        let messages = self.received.entry((round, phase)).or_default();
+        if messages.iter().all(|&(s, _)| s != sender) {
+            messages.push((sender, value));
+        }
+    }
+
+    fn send(&mut self, phase: Phase, value: Option<bool>) -> Effect<BenOrMessage> {
+        let (id, round) = (self.config.id, self.round);
+        self.phase = phase;
+        self.record(id, round, phase, value);
+        Effect::Broadcast(BenOrMessage {
+            sender: id,
+            round,
+            phase,
+            value,
+        })
+    }
+
+    fn advance(&mut self) -> Vec<Effect<BenOrMessage>> {
+        let n = self.config.total_nodes;
+        let f = self.config.faulty;
+        let mut effects = vec![];
+        while !self.halted {
+            let messages = match self.received.get(&(self.round, self.phase)) {
+                Some(messages) if messages.len() >= n - f => messages,
+                _ => break,
+            };
+            let count = |v: bool| messages.iter().filter(|&&(_, m)| m == Some(v)).count();
+            let (trues, falses) = (count(true), count(false));
+            match self.phase {
+                Phase::Report => {
+                    let proposal = if trues * 2 > n {
+                        Some(true)
+                    } else if falses * 2 > n {
+                        Some(false)
+                    } else {
+                        None
+                    };
+                    effects.push(self.send(Phase::Proposal, proposal));
+                },
+                Phase::Proposal => {
+                    let (v, c) = if trues >= falses { (true, trues) } else { (false, falses) };
+                    if c > f {
+                        self.value = v;
+                        if self.decision.is_none() {
+                            self.decision = Some((v, self.round));
+                        }
+                    } else if c > 0 {
+                        self.value = v;
+                    } else {
+                        // the coin depends only on the seed and the round, so
+                        // the node state stays comparable across executions
+                        let seed = self.config.seed ^ u64::from(self.round);
+                        self.value = StdRng::seed_from_u64(seed).gen();
+                    }
+                    // a decided node takes part in one more round,
+                    // so the others are guaranteed to decide too
+                    match self.decision {
+                        Some((_, decided_round)) if decided_round < self.round => {
+                            self.halted = true;
+                        },
+                        _ => {
+                            self.round += 1;
+                            let round = self.round;
+                            self.received.retain(|&(r, _), _| r >= round);
+                            let value = Some(self.value);
+                            effects.push(self.send(Phase::Report, value));
+                        },
+                    }
+                },
+            }
+        }
+        effects
+    }
+}
+
+impl Decisions for BenOrNode {
+    type Value = bool;
+
+    fn decided_levels(&self) -> usize {
+        self.decision.iter().count()
+    }
+
+    fn decision(&self, level: usize) -> Option<Self::Value> {
+        self.decision.filter(|_| level == 0).map(|(value, _)| value)
+    }
+}
+
+impl Node for BenOrNode {
+    type Message = BenOrMessage;
+    type Input = ();
+
+    fn handle_event(
+        &mut self,
+        time: Duration,
+        event: &NodeEvent<Self::Message, Self::Input>,
+    ) -> Vec<Effect<Self::Message>> {
+        let _ = time;
+        if self.halted {
+            return vec![];
+        }
+        match *event {
+            NodeEvent::Start { .. } => {
+                self.round = 1;
+                let value = Some(self.value);
+                let mut effects = vec![self.send(Phase::Report, value)];
+                effects.extend(self.advance());
+                effects
+            },
+            NodeEvent::WakeUp | NodeEvent::Input(()) => vec![],
+            NodeEvent::MessageReceived(ref message) => {
+                self.record(message.sender, message.round, message.phase, message.value);
+                if self.round == 0 {
+                    vec![]
+                } else {
+                    self.advance()
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{Simulator, CaseNetwork, DelayModel, Agreement};
+    use super::{BenOrNode, BenOrConfig};
+
+    // the first `faulty` nodes crash before they start
+    fn run(configs: Vec<BenOrConfig>, faulty: usize, seed: u64) -> Vec<Option<BenOrNode>> {
+        let delay = DelayModel::Uniform {
+            min: Duration::from_millis(1),
+            max: Duration::from_millis(100),
+        };
+        let mut simulator = Simulator::new(configs.into_iter().map(BenOrNode::new), CaseNetwork::new(delay, seed));
+        for node_id in 0..faulty {
+            simulator.shut_down(node_id);
+        }
+        simulator
+            .run_checked(100_000, &mut (), &mut Agreement::new())
+            .unwrap();
+        simulator.nodes().map(|node| node.cloned()).collect()
+    }
+
+    #[test]
+    fn decides_with_crashed_nodes() {
+        // crashed nodes only need n > 2f, down to the boundary
+        for &(total_nodes, faulty) in &[(3, 1), (5, 2), (6, 1), (7, 3), (11, 2), (16, 3)] {
+            for seed in 0..20 {
+                let configs = BenOrConfig::new(total_nodes, faulty, seed).collect();
+                let nodes = run(configs, faulty, seed);
+                let decisions = nodes
+                    .iter()
+                    .flatten()
+                    .map(|node| node.decided().map(|(value, _)| value))
+                    .collect::<Vec<_>>();
+                assert_eq!(decisions.len(), total_nodes - faulty);
+                assert!(decisions.iter().all(Option::is_some), "n={} f={} seed={}", total_nodes, faulty, seed);
+                assert!(decisions.windows(2).all(|pair| pair[0] == pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn unanimous_input_is_decided_in_first_round() {
+        for &initial in &[false, true] {
+            let configs = BenOrConfig::new(6, 1, 7)
+                .map(|config| BenOrConfig { initial, ..config })
+                .collect();
+            for node in run(configs, 1, 7).iter().flatten() {
+                assert_eq!(node.decided(), Some((initial, 1)));
+            }
+        }
+    }
+}
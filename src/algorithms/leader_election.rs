@@ -1,18 +1,21 @@
 use std::time::Duration;
-use crate::{Node, NodeEvent, Effect};
+use crate::{Node, NodeEvent, Effect, Decisions};
 
+#[derive(Clone, Hash)]
 pub struct LeaderElectionNode {
-    num: usize,
     inner: State,
 }
 
+#[derive(Clone, Hash)]
 enum State {
     Good(GoodNode),
 }
 
+#[derive(Clone, Hash)]
 enum GoodNode {
+    Idle,
     Alive {
-        this_id: usize,
+        num: usize,
         seen: Vec<usize>,
     },
     Decided {
@@ -21,35 +24,66 @@ enum GoodNode {
 }
 
 impl LeaderElectionNode {
-    pub fn good(this_id: usize, num: usize) -> Self {
+    pub fn good() -> Self {
         LeaderElectionNode {
-            inner: State::Good(GoodNode::Alive {
-                this_id,
-                seen: vec![this_id],
-            }),
-            num,
+            inner: State::Good(GoodNode::Idle),
         }
     }
 }
 
+impl LeaderElectionNode {
+    pub fn leader(&self) -> Option<usize> {
+        match &self.inner {
+            &State::Good(GoodNode::Decided { leader }) => Some(leader),
+            _ => None,
+        }
+    }
+}
+
+impl Decisions for LeaderElectionNode {
+    type Value = usize;
+
+    fn decided_levels(&self) -> usize {
+        self.leader().iter().count()
+    }
+
+    fn decision(&self, level: usize) -> Option<Self::Value> {
+        self.leader().filter(|_| level == 0)
+    }
+}
+
 impl Node for LeaderElectionNode {
     type Message = usize;
+    type Input = ();
 
     fn handle_event(
         &mut self,
         time: Duration,
-        event: &NodeEvent<Self::Message>,
+        event: &NodeEvent<Self::Message, Self::Input>,
     ) -> Vec<Effect<Self::Message>> {
         let _ = time;
-        let num = self.num;
         match &mut self.inner {
             &mut State::Good(ref mut state) => match state {
-                &mut GoodNode::Alive { ref this_id, ref mut seen } => {
-                    match event {
-                        &NodeEvent::WakeUp => vec![Effect::Broadcast(*this_id)],
-                        &NodeEvent::MessageReceived(id) => {
+                GoodNode::Idle => {
+                    match *event {
+                        NodeEvent::Start { node_id, node_number } => {
+                            *state = GoodNode::Alive {
+                                num: node_number,
+                                seen: vec![node_id],
+                            };
+                            vec![Effect::Broadcast(node_id)]
+                        },
+                        _ => vec![],
+                    }
+                },
+                GoodNode::Alive { num, seen } => {
+                    match *event {
+                        NodeEvent::Start { .. } => vec![],
+                        NodeEvent::WakeUp => vec![],
+                        NodeEvent::Input(()) => vec![],
+                        NodeEvent::MessageReceived(id) => {
                             seen.push(id);
-                            if seen.len() == num {
+                            if seen.len() == *num {
                                 *state = GoodNode::Decided {
                                     leader: *seen.iter().max().unwrap(),
                                 };
@@ -60,7 +94,7 @@ impl Node for LeaderElectionNode {
                         }
                     }
                 },
-                &mut GoodNode::Decided { leader } => {
+                GoodNode::Decided { leader } => {
                     let _ = leader;
                     vec![]
                 }
@@ -1,4 +1,8 @@
 pub mod leader_election;
 
+pub mod ben_or;
+
+pub mod streamlet;
+
 #[cfg(feature = "tenderbake")]
 pub mod tenderbake;
@@ -0,0 +1,319 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+use crate::{Node, NodeEvent, Effect, MessageSize, MessageKind, Decisions};
+
+#[derive(Clone, Hash)]
+pub struct StreamletConfig {
+    pub id: usize,
+    pub total_nodes: usize,
+    pub epoch_duration: Duration,
+}
+
+impl StreamletConfig {
+    pub fn new(total_nodes: usize, epoch_duration: Duration) -> impl Iterator<Item = Self> {
+        (0..total_nodes)
+            .map(move |id| StreamletConfig {
+                id,
+                total_nodes,
+                epoch_duration,
+            })
+    }
+}
+
+pub type BlockHash = u64;
+
+const GENESIS: BlockHash = 0;
+
+#[derive(Debug, Clone, Hash)]
+pub struct Block {
+    parent: BlockHash,
+    epoch: u64,
+    proposer: usize,
+}
+
+impl Block {
+    pub fn hash(&self) -> BlockHash {
+        let mut hasher = DefaultHasher::new();
+        Hash::hash(self, &mut hasher);
+        hasher.finish()
+    }
+
+    pub fn parent(&self) -> BlockHash {
+        self.parent
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn proposer(&self) -> usize {
+        self.proposer
+    }
+}
+
+#[derive(Debug, Clone, Hash)]
+pub enum StreamletMessage {
+    // the proposal counts as the proposer's vote
+    Propose(Block),
+    Vote {
+        voter: usize,
+        hash: BlockHash,
+    },
+}
+
+impl MessageSize for StreamletMessage {
+    fn size(&self) -> usize {
+        match self {
+            // parent hash, epoch and proposer
+            StreamletMessage::Propose(_) => 8 + 8 + 4,
+            // voter and hash
+            StreamletMessage::Vote { .. } => 4 + 8,
+        }
+    }
+}
+
+impl MessageKind for StreamletMessage {
+    type Proposal = BlockHash;
+
+    fn kind(&self) -> &'static str {
+        match self {
+            StreamletMessage::Propose(_) => "propose",
+            StreamletMessage::Vote { .. } => "vote",
+        }
+    }
+
+    fn proposal(&self) -> Option<(Self::Proposal, u64)> {
+        match self {
+            StreamletMessage::Propose(block) => Some((block.hash(), 0)),
+            StreamletMessage::Vote { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, Hash)]
+pub struct StreamletNode {
+    config: StreamletConfig,
+    epoch: u64,
+    voted_epoch: u64,
+    blocks: BTreeMap<BlockHash, Block>,
+    votes: BTreeMap<BlockHash, BTreeSet<usize>>,
+    // blocks whose whole chain down to genesis is notarized, with their height
+    notarized: BTreeMap<BlockHash, u64>,
+    finalized: Vec<Block>,
+}
+
+impl StreamletNode {
+    pub fn new(config: StreamletConfig) -> Self {
+        let mut notarized = BTreeMap::new();
+        notarized.insert(GENESIS, 0);
+        StreamletNode {
+            config,
+            epoch: 0,
+            voted_epoch: 0,
+            blocks: BTreeMap::new(),
+            votes: BTreeMap::new(),
+            notarized,
+            finalized: vec![],
+        }
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn finalized(&self) -> &[Block] {
+        &self.finalized
+    }
+
+    fn leader(&self, epoch: u64) -> usize {
+        (epoch as usize) % self.config.total_nodes
+    }
+
+    fn longest_notarized(&self) -> (BlockHash, u64) {
+        self.notarized
+            .iter()
+            .fold((GENESIS, 0), |(h, l), (&hash, &height)| {
+                if height > l { (hash, height) } else { (h, l) }
+            })
+    }
+
+    fn vote(&mut self, voter: usize, hash: BlockHash) {
+        if self.votes.entry(hash).or_default().insert(voter) {
+            self.try_notarize(hash);
+        }
+    }
+
+    fn try_notarize(&mut self, hash: BlockHash) {
+        let enough_votes = self.votes
+            .get(&hash)
+            .map(|votes| votes.len() * 3 >= self.config.total_nodes * 2)
+            .unwrap_or(false);
+        if !enough_votes || self.notarized.contains_key(&hash) {
+            return;
+        }
+        let parent_height = match self.blocks.get(&hash) {
+            Some(block) => match self.notarized.get(&block.parent) {
+                Some(&height) => height,
+                None => return,
+            },
+            None => return,
+        };
+        self.notarized.insert(hash, parent_height + 1);
+        self.try_finalize(hash);
+
+        // children that were notarized before this block are now on a notarized chain
+        let children = self.blocks
+            .iter()
+            .filter(|&(_, block)| block.parent == hash)
+            .map(|(&child, _)| child)
+            .collect::<Vec<_>>();
+        for child in children {
+            self.try_notarize(child);
+        }
+    }
+
+    fn try_finalize(&mut self, hash: BlockHash) {
+        let third = &self.blocks[&hash];
+        let second = match self.blocks.get(&third.parent) {
+            Some(block) if block.epoch + 1 == third.epoch => block,
+            _ => return,
+        };
+        // genesis is at epoch zero, so it can be the first of the three
+        let first_epoch = match second.parent {
+            GENESIS => Some(0),
+            parent => self.blocks.get(&parent).map(Block::epoch),
+        };
+        match first_epoch {
+            Some(epoch) if epoch + 1 == second.epoch => (),
+            _ => return,
+        }
+
+        // finalize the chain up to and including the second block
+        let mut chain = vec![];
+        let mut cursor = third.parent;
+        while cursor != GENESIS {
+            let block = &self.blocks[&cursor];
+            chain.push(block.clone());
+            cursor = block.parent;
+        }
+        if chain.len() > self.finalized.len() {
+            chain.reverse();
+            self.finalized = chain;
+        }
+    }
+
+    fn on_block(&mut self, block: Block) -> Vec<Effect<StreamletMessage>> {
+        let hash = block.hash();
+        let (epoch, proposer, parent) = (block.epoch, block.proposer, block.parent);
+        self.blocks.entry(hash).or_insert(block);
+
+        let mut effects = vec![];
+        let (_, longest) = self.longest_notarized();
+        let extends_longest = self.notarized.get(&parent) == Some(&longest);
+        let ok = epoch == self.epoch
+            && proposer == self.leader(epoch)
+            && self.voted_epoch < epoch
+            && extends_longest;
+        if ok {
+            self.voted_epoch = epoch;
+            let voter = self.config.id;
+            effects.push(Effect::Broadcast(StreamletMessage::Vote { voter, hash }));
+            self.vote(voter, hash);
+        }
+        self.vote(proposer, hash);
+        self.try_notarize(hash);
+        effects
+    }
+
+    fn on_epoch(&mut self, epoch: u64) -> Vec<Effect<StreamletMessage>> {
+        self.epoch = epoch;
+        if self.leader(epoch) == self.config.id {
+            self.voted_epoch = epoch;
+            let (parent, _) = self.longest_notarized();
+            let block = Block {
+                parent,
+                epoch,
+                proposer: self.config.id,
+            };
+            let mut effects = vec![Effect::Broadcast(StreamletMessage::Propose(block.clone()))];
+            effects.extend(self.on_block(block));
+            effects
+        } else {
+            vec![]
+        }
+    }
+}
+
+impl Decisions for StreamletNode {
+    type Value = BlockHash;
+
+    fn decided_levels(&self) -> usize {
+        self.finalized.len()
+    }
+
+    fn decision(&self, level: usize) -> Option<Self::Value> {
+        self.finalized.get(level).map(Block::hash)
+    }
+}
+
+impl Node for StreamletNode {
+    type Message = StreamletMessage;
+    type Input = ();
+
+    fn handle_event(
+        &mut self,
+        time: Duration,
+        event: &NodeEvent<Self::Message, Self::Input>,
+    ) -> Vec<Effect<Self::Message>> {
+        match *event {
+            NodeEvent::Start { .. } | NodeEvent::WakeUp => {
+                let d = self.config.epoch_duration.as_nanos();
+                let epoch = (time.as_nanos() / d) as u64 + 1;
+                let mut effects = if epoch > self.epoch {
+                    self.on_epoch(epoch)
+                } else {
+                    vec![]
+                };
+                let next_epoch_start = self.config.epoch_duration * (epoch as u32);
+                effects.push(Effect::SetWakeUpTime(next_epoch_start));
+                effects
+            },
+            NodeEvent::MessageReceived(StreamletMessage::Propose(ref block)) => {
+                self.on_block(block.clone())
+            },
+            NodeEvent::MessageReceived(StreamletMessage::Vote { voter, hash }) => {
+                self.vote(voter, hash);
+                vec![]
+            },
+            NodeEvent::Input(()) => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{Simulator, CaseNetwork, DelayModel, Agreement, Invariant};
+    use super::{StreamletNode, StreamletConfig, GENESIS};
+
+    #[test]
+    fn first_block_finalizes_in_second_epoch() {
+        let epoch_duration = Duration::from_secs(1);
+        let network = CaseNetwork::new(DelayModel::Constant(Duration::from_millis(10)), 0);
+        let nodes = StreamletConfig::new(4, epoch_duration).map(StreamletNode::new);
+        let mut simulator = Simulator::new(nodes, network);
+        let mut agreement = Agreement::new();
+        simulator.run_until(10_000, &mut (), |time, nodes| {
+            agreement.check(time, nodes).unwrap();
+            time >= epoch_duration * 3 / 2
+        });
+        for node in simulator.nodes().flatten() {
+            let finalized = node.finalized();
+            assert_eq!(finalized.len(), 1);
+            assert_eq!((finalized[0].parent(), finalized[0].epoch()), (GENESIS, 1));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use super::{
+    blockchain::{Block, BlockHash, Chain},
+    config::TenderbakeConfig,
+};
+
+// every block the node knows together with all its ancestors, competing
+// branches included, the predecessor hash is the parent link; serialized as
+// a list since the keys are not strings
+#[derive(Debug, Clone, Default, Hash, Serialize, Deserialize)]
+#[serde(from = "Vec<Block>", into = "Vec<Block>")]
+pub struct BlockTree {
+    blocks: BTreeMap<BlockHash, Block>,
+}
+
+impl From<Vec<Block>> for BlockTree {
+    fn from(blocks: Vec<Block>) -> Self {
+        BlockTree {
+            blocks: blocks.into_iter().map(|block| (block.hash(), block)).collect(),
+        }
+    }
+}
+
+impl From<BlockTree> for Vec<Block> {
+    fn from(tree: BlockTree) -> Self {
+        tree.blocks.into_values().collect()
+    }
+}
+
+impl BlockTree {
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn contains(&self, hash: &BlockHash) -> bool {
+        self.blocks.contains_key(hash)
+    }
+
+    pub fn get(&self, hash: &BlockHash) -> Option<&Block> {
+        self.blocks.get(hash)
+    }
+
+    // the parent is known, or the block is at the first level
+    pub fn is_connected(&self, block: &Block) -> bool {
+        match block.contents().predecessor_hash() {
+            None => block.contents().level() == 1,
+            Some(hash) => self.contains(hash),
+        }
+    }
+
+    // the block extends its parent: the levels follow each other and the
+    // block carries the endorsement quorum of the parent
+    pub fn is_valid_successor(&self, block: &Block, config: &TenderbakeConfig) -> bool {
+        let parent = block.contents().predecessor_hash().and_then(|hash| self.get(hash));
+        let chain = match parent {
+            None if block.contents().level() == 1 => Chain::from(vec![block.clone()]),
+            None => return false,
+            Some(parent) if parent.contents().level() + 1 == block.contents().level() => {
+                Chain::from(vec![parent.clone(), block.clone()])
+            },
+            Some(_) => return false,
+        };
+        block.is_pqc_valid(config) && chain.valid_chain(config)
+    }
+
+    // keeps the block already stored under the same hash, returns false then,
+    // or if the parent is missing
+    pub fn insert(&mut self, block: Block) -> bool {
+        if !self.is_connected(&block) {
+            return false;
+        }
+        let hash = block.hash();
+        if self.blocks.contains_key(&hash) {
+            return false;
+        }
+        self.blocks.insert(hash, block);
+        true
+    }
+
+    // the same contents proposed again in a later round
+    pub fn replace(&mut self, block: Block) {
+        if self.is_connected(&block) {
+            self.blocks.insert(block.hash(), block);
+        }
+    }
+
+    // from the block with the given hash down to the first level, or to the
+    // first missing block
+    pub fn ancestors<'a>(&'a self, hash: Option<&'a BlockHash>) -> impl Iterator<Item = (&'a BlockHash, &'a Block)> {
+        let mut next = hash;
+        std::iter::from_fn(move || {
+            let (hash, block) = self.blocks.get_key_value(next?)?;
+            next = block.contents().predecessor_hash();
+            Some((hash, block))
+        })
+    }
+
+    // the last two blocks of the branch, enough for the fork choice
+    pub fn tip(&self, hash: Option<&BlockHash>) -> Chain {
+        let mut tip = self.ancestors(hash)
+            .take(2)
+            .map(|(_, block)| block.clone())
+            .collect::<Vec<_>>();
+        tip.reverse();
+        Chain::from(tip)
+    }
+
+    // the blocks of the branch above `level`, in ascending order
+    pub fn segment(&self, hash: &BlockHash, level: u32) -> Vec<Block> {
+        let mut segment = self.ancestors(Some(hash))
+            .map(|(_, block)| block)
+            .take_while(|block| block.contents().level() > level)
+            .cloned()
+            .collect::<Vec<_>>();
+        segment.reverse();
+        segment
+    }
+}
@@ -1,30 +1,135 @@
-use std::time::Duration;
+use std::{convert::TryFrom, error::Error, fmt, time::Duration};
 use crypto::blake2b;
 use tezos_messages::p2p::binary_message::BinaryWrite;
-use tezos_encoding::encoding::HasEncoding;
-use serde::Serialize;
+use tezos_encoding::{binary_reader::BinaryReaderError, encoding::HasEncoding, nom::NomReader};
+use serde::{Serialize, Deserialize};
+use crate::MessageSize;
 use super::config::TenderbakeConfig;
 
-#[derive(Debug, Clone, HasEncoding, Serialize, PartialEq, Eq)]
+pub const HASH_SIZE: usize = 32;
+pub const SIGNATURE_SIZE: usize = 64;
+
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct BlockHash {
+    #[encoding(sized = "HASH_SIZE", bytes)]
+    bytes: Vec<u8>,
+}
+
+impl MessageSize for BlockHash {
+    fn size(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StateHash {
+    #[encoding(sized = "HASH_SIZE", bytes)]
     bytes: Vec<u8>,
 }
 
-#[derive(Debug, Clone, HasEncoding, Serialize)]
+impl StateHash {
+    pub fn digest(bytes: &[u8]) -> Self {
+        StateHash {
+            bytes: blake2b::digest_256(bytes).unwrap(),
+        }
+    }
+}
+
+impl MessageSize for StateHash {
+    fn size(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+// a transfer between two accounts of the ledger, the nonce is the number of
+// transfers the sender has made before
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Transaction {
     #[encoding(builtin = "Uint32")]
-    x: u32,
+    sender: u32,
+    #[encoding(builtin = "Uint32")]
+    receiver: u32,
+    #[encoding(builtin = "Uint32")]
+    amount: u32,
+    #[encoding(builtin = "Uint32")]
+    nonce: u32,
 }
 
-#[derive(Debug, Clone, HasEncoding, Serialize)]
+impl Transaction {
+    pub fn new(sender: u32, receiver: u32, amount: u32, nonce: u32) -> Self {
+        Transaction {
+            sender,
+            receiver,
+            amount,
+            nonce,
+        }
+    }
+
+    pub fn sender(&self) -> u32 {
+        self.sender
+    }
+
+    pub fn receiver(&self) -> u32 {
+        self.receiver
+    }
+
+    pub fn amount(&self) -> u32 {
+        self.amount
+    }
+
+    pub fn nonce(&self) -> u32 {
+        self.nonce
+    }
+}
+
+impl MessageSize for Transaction {
+    fn size(&self) -> usize {
+        self.as_bytes().unwrap().len()
+    }
+}
+
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
 pub struct BlockContents {
+    #[encoding(dynamic, list)]
     transactions: Vec<Transaction>,
     #[encoding(builtin = "Uint32")]
     level: u32,
     predecessor_hash: Option<BlockHash>,
+    // the ledger after the transactions of this block are applied
+    state_hash: StateHash,
 }
 
 impl BlockContents {
+    pub fn new(
+        transactions: Vec<Transaction>,
+        level: u32,
+        predecessor_hash: Option<BlockHash>,
+        state_hash: StateHash,
+    ) -> Self {
+        BlockContents {
+            transactions,
+            level,
+            predecessor_hash,
+            state_hash,
+        }
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn predecessor_hash(&self) -> Option<&BlockHash> {
+        self.predecessor_hash.as_ref()
+    }
+
+    pub fn state_hash(&self) -> &StateHash {
+        &self.state_hash
+    }
+
     pub fn hash(&self) -> BlockHash {
         BlockHash {
             bytes: blake2b::digest_256(&self.as_bytes().unwrap()).unwrap(),
@@ -32,44 +137,218 @@ impl BlockContents {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Signature(usize);
+impl MessageSize for BlockContents {
+    fn size(&self) -> usize {
+        self.as_bytes().unwrap().len()
+    }
+}
+
+// signatures are not simulated, the signer is trusted; the bytes are zeros,
+// they are there for the encoded size
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+pub struct Signature {
+    #[encoding(builtin = "Uint32")]
+    signer: u32,
+    #[encoding(sized = "SIGNATURE_SIZE", bytes)]
+    bytes: Vec<u8>,
+}
 
-#[derive(Debug, Clone)]
-pub struct Signed<T>(Signature, T);
+impl Signature {
+    pub fn sign(signer_id: usize) -> Self {
+        Signature {
+            signer: signer_id as u32,
+            bytes: vec![0; SIGNATURE_SIZE],
+        }
+    }
 
-impl<T> Signed<T> {
-    pub fn check(self) -> Option<(T, usize)> {
-        let Signed(Signature(signer_id), t) = self;
-        Some((t, signer_id))
+    pub fn signer(&self) -> usize {
+        self.signer as usize
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Preendorsement(pub Signed<BlockHash>);
+// a vote is bound to the level and the round it is cast in, the signature
+// covers them, as in a Tezos consensus operation
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+pub struct Preendorsement {
+    #[encoding(builtin = "Uint32")]
+    level: u32,
+    #[encoding(builtin = "Uint32")]
+    round: u32,
+    block_hash: BlockHash,
+    signature: Signature,
+}
+
+impl Preendorsement {
+    pub fn new(signer_id: usize, level: u32, round: u64, block_hash: BlockHash) -> Self {
+        Preendorsement {
+            level,
+            round: wire_round(round),
+            block_hash,
+            signature: Signature::sign(signer_id),
+        }
+    }
 
-impl AsRef<Signed<BlockHash>> for Preendorsement {
-    fn as_ref(&self) -> &Signed<BlockHash> {
-        &self.0
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn round(&self) -> u64 {
+        u64::from(self.round)
+    }
+
+    pub fn block_hash(&self) -> &BlockHash {
+        &self.block_hash
+    }
+
+    pub fn signer(&self) -> usize {
+        self.signature.signer()
+    }
+
+    fn vote(&self) -> Vote<'_> {
+        (self.signer(), self.level, self.round(), &self.block_hash)
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Endorsement(pub Signed<BlockHash>);
+impl MessageSize for Preendorsement {
+    fn size(&self) -> usize {
+        self.as_bytes().unwrap().len()
+    }
+}
 
-impl AsRef<Signed<BlockHash>> for Endorsement {
-    fn as_ref(&self) -> &Signed<BlockHash> {
-        &self.0
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+pub struct Endorsement {
+    #[encoding(builtin = "Uint32")]
+    level: u32,
+    #[encoding(builtin = "Uint32")]
+    round: u32,
+    block_hash: BlockHash,
+    signature: Signature,
+}
+
+impl Endorsement {
+    pub fn new(signer_id: usize, level: u32, round: u64, block_hash: BlockHash) -> Self {
+        Endorsement {
+            level,
+            round: wire_round(round),
+            block_hash,
+            signature: Signature::sign(signer_id),
+        }
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    pub fn round(&self) -> u64 {
+        u64::from(self.round)
+    }
+
+    pub fn block_hash(&self) -> &BlockHash {
+        &self.block_hash
+    }
+
+    pub fn signer(&self) -> usize {
+        self.signature.signer()
+    }
+
+    fn vote(&self) -> Vote<'_> {
+        (self.signer(), self.level, self.round(), &self.block_hash)
+    }
+}
+
+impl MessageSize for Endorsement {
+    fn size(&self) -> usize {
+        self.as_bytes().unwrap().len()
     }
 }
 
-#[derive(Debug, Clone)]
+// the signer, level, round and block of a vote
+type Vote<'a> = (usize, u32, u64, &'a BlockHash);
+
+// the preendorsement quorum a block carries when its contents were already
+// proposed in an earlier round of the level, the round is the one its
+// votes were cast in
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+pub struct QuorumCertificate {
+    #[encoding(dynamic, list)]
+    preendorsements: Vec<Preendorsement>,
+}
+
+impl QuorumCertificate {
+    pub fn new(preendorsements: Vec<Preendorsement>) -> Self {
+        QuorumCertificate { preendorsements }
+    }
+
+    // none unless every vote is of the same round
+    pub fn round(&self) -> Option<u64> {
+        common_round(self.preendorsements.iter().map(Preendorsement::vote))
+    }
+
+    pub fn preendorsements(&self) -> &[Preendorsement] {
+        &self.preendorsements
+    }
+
+    // the round a quorum preendorsed the contents in, none if it did not
+    pub fn quorum_round(&self, config: &TenderbakeConfig, contents: &BlockContents) -> Option<u64> {
+        quorum_round(config, contents, self.preendorsements.iter().map(Preendorsement::vote))
+    }
+}
+
+fn common_round<'a>(mut votes: impl Iterator<Item = Vote<'a>>) -> Option<u64> {
+    let (_, _, round, _) = votes.next()?;
+    if votes.all(|(_, _, r, _)| r == round) {
+        Some(round)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
 pub struct Block {
     contents: BlockContents,
-    round: u64,
-    timestamp: Duration,
+    #[encoding(builtin = "Uint32")]
+    round: u32,
+    // nanoseconds, there is no encoding for `Duration`, signed as Tezos
+    // timestamps are, a decoded block with a negative one is rejected
+    #[encoding(builtin = "Int64")]
+    timestamp: i64,
+    #[encoding(dynamic, list)]
     predecessor_eqc: Vec<Endorsement>,
-    previously_proposed: Option<(u64, Vec<Preendorsement>)>
+    previously_proposed: Option<QuorumCertificate>,
+}
+
+impl MessageSize for Block {
+    fn size(&self) -> usize {
+        self.as_bytes().unwrap().len()
+    }
+}
+
+// rounds are `u64` in the API and 32 bits on the wire, as in Tezos
+pub(crate) fn wire_round(round: u64) -> u32 {
+    u32::try_from(round).expect("the round fits the encoding")
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Encoding(BinaryReaderError),
+    NegativeTimestamp(i64),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Encoding(error) => write!(f, "{}", error),
+            DecodeError::NegativeTimestamp(timestamp) => write!(f, "negative timestamp {}", timestamp),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<BinaryReaderError> for DecodeError {
+    fn from(error: BinaryReaderError) -> Self {
+        DecodeError::Encoding(error)
+    }
 }
 
 fn is_committee_member(node_id: usize, total_nodes: usize, level: u32) -> bool {
@@ -77,57 +356,122 @@ fn is_committee_member(node_id: usize, total_nodes: usize, level: u32) -> bool {
     true
 }
 
+// the round in which a quorum voted for the contents at their level, a
+// quorum counts distinct signers so a repeated vote does not stand in for a
+// missing one, and its votes are of a single round
+fn quorum_round<'a>(
+    config: &TenderbakeConfig,
+    contents: &BlockContents,
+    votes: impl Iterator<Item = Vote<'a>> + Clone,
+) -> Option<u64> {
+    let this_hash = contents.hash();
+    let mut signers = vec![];
+    for (signer_id, level, _, hash) in votes.clone() {
+        let ok = is_committee_member(signer_id, config.total_nodes, contents.level)
+            && level == contents.level
+            && &this_hash == hash;
+        if !ok {
+            return None;
+        }
+        if !signers.contains(&signer_id) {
+            signers.push(signer_id);
+        }
+    }
+    if signers.len() < config.quorum_size {
+        return None;
+    }
+    common_round(votes)
+}
+
 impl Block {
-    pub fn is_pqc_valid(&self, config: &TenderbakeConfig) -> bool {
-        if let &Some((_, ref pqc)) = &self.previously_proposed {
-            self.is_qc_valid(config, pqc.into_iter())
-        } else {
-            true
+    pub fn new(
+        contents: BlockContents,
+        round: u64,
+        timestamp: Duration,
+        predecessor_eqc: Vec<Endorsement>,
+        previously_proposed: Option<QuorumCertificate>,
+    ) -> Self {
+        Block {
+            contents,
+            round: wire_round(round),
+            timestamp: i64::try_from(timestamp.as_nanos()).expect("the timestamp fits the encoding"),
+            predecessor_eqc,
+            previously_proposed,
         }
     }
 
-    pub fn is_eqc_valid(&self, config: &TenderbakeConfig, eqc: &[Endorsement]) -> bool {
-        self.is_qc_valid(config, eqc.into_iter())
-    }
-
-    fn is_qc_valid<'a, C>(
-        &self,
-        config: &TenderbakeConfig,
-        qc: impl Iterator<Item = &'a C> + 'a,
-    ) -> bool
-    where
-        C: 'a + AsRef<Signed<BlockHash>>,
-    {
-        let this_hash = self.contents.hash();
-        for signed in qc {
-            if let Some((hash, signer_id)) = signed.as_ref().clone().check() {
-                let ok = true
-                    && is_committee_member(signer_id, config.total_nodes, self.contents.level)
-                    && this_hash == hash;
-                if !ok {
-                    return false;
-                }
-            } else {
-                return false;
-            }
+    pub fn contents(&self) -> &BlockContents {
+        &self.contents
+    }
+
+    pub fn round(&self) -> u64 {
+        u64::from(self.round)
+    }
+
+    pub fn predecessor_eqc(&self) -> &[Endorsement] {
+        &self.predecessor_eqc
+    }
+
+    pub fn previously_proposed(&self) -> Option<&QuorumCertificate> {
+        self.previously_proposed.as_ref()
+    }
+
+    // the contents were preendorsed by a quorum in an earlier round
+    pub fn is_pqc_valid(&self, config: &TenderbakeConfig) -> bool {
+        match self.previously_proposed {
+            Some(ref pqc) => pqc
+                .quorum_round(config, &self.contents)
+                .map(|round| round < self.round())
+                .unwrap_or(false),
+            None => true,
         }
+    }
 
-        true
+    pub fn is_eqc_valid(&self, config: &TenderbakeConfig, eqc: &[Endorsement]) -> bool {
+        quorum_round(config, &self.contents, eqc.iter().map(Endorsement::vote)).is_some()
     }
 
     pub fn timestamp(&self) -> Duration {
-        self.timestamp
+        Duration::from_nanos(u64::try_from(self.timestamp).expect("the timestamp is checked"))
+    }
+
+    // what the encoding admits but a block cannot have
+    pub fn check(&self) -> Result<(), DecodeError> {
+        if self.timestamp < 0 {
+            return Err(DecodeError::NegativeTimestamp(self.timestamp));
+        }
+        Ok(())
+    }
+
+    pub fn hash(&self) -> BlockHash {
+        self.contents.hash()
     }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Hash, Serialize, Deserialize)]
 pub struct Chain(Vec<Block>);
 
+impl MessageSize for Chain {
+    fn size(&self) -> usize {
+        self.0.iter().map(MessageSize::size).sum()
+    }
+}
+
+impl From<Vec<Block>> for Chain {
+    fn from(blocks: Vec<Block>) -> Self {
+        Chain(blocks)
+    }
+}
+
 impl Chain {
     pub fn head(&self) -> Option<&Block> {
         self.0.last()
     }
 
+    pub fn blocks(&self) -> &[Block] {
+        &self.0
+    }
+
     pub fn valid_chain(&self, config: &TenderbakeConfig) -> bool {
         fn inner(
             chain: &[Block],
@@ -160,7 +504,7 @@ impl Chain {
         inner(&self.0, config, None, None)
     }
 
-    fn level(&self) -> u32 {
+    pub fn level(&self) -> u32 {
         match self.0.last() {
             None => 0,
             Some(head) => head.contents.level,
@@ -180,9 +524,13 @@ impl Chain {
                         _ => false,
                     };
 
-                    match (candidate_head.previously_proposed, round) {
+                    let candidate_endorsable_round = candidate_head
+                        .previously_proposed
+                        .as_ref()
+                        .and_then(QuorumCertificate::round);
+                    match (candidate_endorsable_round, round.map(|round| round.round_id)) {
                         (None, None) => node_predecessor_round_is_higher,
-                        (Some((candidate_endorsable_round, _)), Some((node_endorsable_round, _))) => {
+                        (Some(candidate_endorsable_round), Some(node_endorsable_round)) => {
                             if candidate_endorsable_round == node_endorsable_round {
                                 node_predecessor_round_is_higher
                             } else {
@@ -192,12 +540,6 @@ impl Chain {
                         (Some(_), None) => true,
                         (None, Some(_)) => false,
                     }
-
-                    let _ = (current, round);
-                    if let Some(round) = round {
-                        let _ = (&round.round_id, &round.block_contents, &round.quorum_certificate);
-                    }
-                    unimplemented!()
                 },
                 _ => false,
             }
@@ -207,8 +549,117 @@ impl Chain {
     }
 }
 
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
 pub struct Round {
-    round_id: usize,
+    round_id: u64,
     block_contents: BlockContents,
     quorum_certificate: Vec<Preendorsement>,
 }
+
+impl Round {
+    pub fn new(round_id: u64, block_contents: BlockContents, quorum_certificate: Vec<Preendorsement>) -> Self {
+        Round {
+            round_id,
+            block_contents,
+            quorum_certificate,
+        }
+    }
+
+    pub fn round_id(&self) -> u64 {
+        self.round_id
+    }
+
+    pub fn block_contents(&self) -> &BlockContents {
+        &self.block_contents
+    }
+
+    pub fn quorum_certificate(&self) -> &[Preendorsement] {
+        &self.quorum_certificate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{
+        Block, BlockContents, BlockHash, StateHash, Endorsement, Preendorsement, QuorumCertificate, TenderbakeConfig,
+        DecodeError,
+    };
+
+    fn block() -> Block {
+        let contents = BlockContents::new(vec![], 1, None, StateHash::digest(&[]));
+        Block::new(contents, 0, Duration::default(), vec![], None)
+    }
+
+    fn eqc(block: &Block, signers: &[usize]) -> Vec<Endorsement> {
+        signers.iter().map(|&id| Endorsement::new(id, 1, 0, block.hash())).collect()
+    }
+
+    #[test]
+    fn quorum_is_more_than_two_thirds() {
+        let quorum = |n| TenderbakeConfig::new(n, Duration::from_secs(1), 0).next().unwrap().quorum_size;
+        assert_eq!(quorum(1), 1);
+        assert_eq!(quorum(3), 3);
+        assert_eq!(quorum(4), 3);
+        assert_eq!(quorum(7), 5);
+        assert_eq!(quorum(10), 7);
+    }
+
+    #[test]
+    fn quorum_certificate_needs_distinct_signers() {
+        let config = TenderbakeConfig::new(4, Duration::from_secs(1), 0).next().unwrap();
+        let block = block();
+        assert!(block.is_eqc_valid(&config, &eqc(&block, &[0, 1, 2])));
+        assert!(block.is_eqc_valid(&config, &eqc(&block, &[3, 1, 2, 0])));
+        assert!(!block.is_eqc_valid(&config, &eqc(&block, &[0, 1])));
+        assert!(!block.is_eqc_valid(&config, &eqc(&block, &[0, 1, 1])));
+
+        let other = BlockHash {
+            bytes: vec![0; super::HASH_SIZE],
+        };
+        let mut votes = eqc(&block, &[0, 1, 2]);
+        votes.push(Endorsement::new(3, 1, 0, other));
+        assert!(!block.is_eqc_valid(&config, &votes));
+    }
+
+    #[test]
+    fn votes_of_a_quorum_share_level_and_round() {
+        let config = TenderbakeConfig::new(4, Duration::from_secs(1), 0).next().unwrap();
+        let block = block();
+        let vote = |id, level, round| Endorsement::new(id, level, round, block.hash());
+        assert!(block.is_eqc_valid(&config, &[vote(0, 1, 2), vote(1, 1, 2), vote(2, 1, 2)]));
+        assert!(!block.is_eqc_valid(&config, &[vote(0, 1, 2), vote(1, 1, 2), vote(2, 1, 3)]));
+        assert!(!block.is_eqc_valid(&config, &[vote(0, 1, 2), vote(1, 1, 2), vote(2, 2, 2)]));
+    }
+
+    // the round of the certificate is the one its votes were cast in, it
+    // must be before the round of the block
+    #[test]
+    fn previously_proposed_round_comes_from_the_votes() {
+        let config = TenderbakeConfig::new(4, Duration::from_secs(1), 0).next().unwrap();
+        let contents = BlockContents::new(vec![], 1, None, StateHash::digest(&[]));
+        let pqc = |rounds: &[u64]| {
+            let vote = |(id, &round)| Preendorsement::new(id, 1, round, contents.hash());
+            QuorumCertificate::new(rounds.iter().enumerate().map(vote).collect())
+        };
+        let block = |round, pqc| Block::new(contents.clone(), round, Duration::default(), vec![], Some(pqc));
+
+        assert_eq!(pqc(&[1, 1, 1]).round(), Some(1));
+        assert_eq!(pqc(&[1, 1, 2]).round(), None);
+        assert!(block(2, pqc(&[1, 1, 1])).is_pqc_valid(&config));
+        assert!(!block(2, pqc(&[1, 1, 2])).is_pqc_valid(&config));
+        assert!(!block(1, pqc(&[1, 1, 1])).is_pqc_valid(&config));
+        assert!(!block(2, QuorumCertificate::new(vec![])).is_pqc_valid(&config));
+        let wrong_level = (0..3).map(|id| Preendorsement::new(id, 2, 0, contents.hash())).collect();
+        assert!(!block(1, QuorumCertificate::new(wrong_level)).is_pqc_valid(&config));
+    }
+
+    // the encoding admits it, only `Block::new` rules it out
+    #[test]
+    fn negative_timestamp_is_rejected() {
+        let mut block = block();
+        assert!(block.check().is_ok());
+        block.timestamp = -1;
+        assert!(matches!(block.check(), Err(DecodeError::NegativeTimestamp(-1))));
+    }
+}
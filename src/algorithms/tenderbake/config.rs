@@ -1,10 +1,17 @@
 use std::time::Duration;
+use serde::{Serialize, Deserialize};
 
+#[derive(Clone, Hash, Serialize, Deserialize)]
 pub struct TenderbakeConfig {
     pub id: usize,
     pub total_nodes: usize,
     pub quorum_size: usize,
     pub round0_duration: Duration,
+    // total size of the transactions in a block, in bytes
+    pub max_block_size: usize,
+    // the genesis ledger, every account holds the same balance
+    pub accounts: u32,
+    pub initial_balance: u64,
     pub seed: u64,
 }
 
@@ -14,7 +21,11 @@ impl TenderbakeConfig {
         round0_duration: Duration,
         seed: u64,
     ) -> impl Iterator<Item = Self> {
-        let quorum_size = total_nodes / 3 + 1;
+        // more than two thirds of the committee: any two quorums share more
+        // than a third of it, so with less than a third faulty they share a
+        // correct node, and it does not vote for two blocks in a round; with
+        // `total_nodes / 3 + 1` two disjoint quorums could decide two blocks
+        let quorum_size = total_nodes * 2 / 3 + 1;
         let mut rng = <rand::rngs::StdRng as rand::SeedableRng>::seed_from_u64(seed);
         (0..total_nodes)
             .map(move |id| TenderbakeConfig {
@@ -22,6 +33,9 @@ impl TenderbakeConfig {
                 total_nodes,
                 quorum_size,
                 round0_duration,
+                max_block_size: 64 * 1024,
+                accounts: 64,
+                initial_balance: 1_000_000,
                 seed: rand::Rng::gen(&mut rng),
             })
     }
@@ -0,0 +1,86 @@
+use std::time::Duration;
+use crate::Observer;
+use super::{blockchain::Block, node::TenderbakeNode};
+
+// a block a node has decided, reported once per node and level
+pub struct Finalized<'a> {
+    pub time: Duration,
+    pub node_id: usize,
+    pub level: u32,
+    pub block: &'a Block,
+}
+
+// calls the hook for every block a node finalizes, it is an observer
+// rather than a field of the node so the node stays `Clone` and `Hash`
+pub struct FinalityHook<F> {
+    hook: F,
+    finalized: Vec<u32>,
+}
+
+impl<F> FinalityHook<F> {
+    pub fn new(hook: F) -> Self {
+        FinalityHook {
+            hook,
+            finalized: vec![],
+        }
+    }
+}
+
+impl<F> Observer<TenderbakeNode> for FinalityHook<F>
+where
+    F: FnMut(Finalized<'_>),
+{
+    fn on_step(&mut self, time: Duration, nodes: &[Option<&TenderbakeNode>]) {
+        self.finalized.resize(nodes.len(), 0);
+        for (node_id, node) in nodes.iter().enumerate() {
+            let node = match node {
+                Some(node) => node,
+                None => continue,
+            };
+            for level in (self.finalized[node_id] + 1)..=node.finalized_level() {
+                let block = node.finalized_block(level).expect("finalized level must have a block");
+                (self.hook)(Finalized {
+                    time,
+                    node_id,
+                    level,
+                    block,
+                });
+            }
+            self.finalized[node_id] = node.finalized_level();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{Simulator, CaseNetwork, DelayModel};
+    use super::super::{blockchain::BlockHash, config::TenderbakeConfig};
+    use super::{FinalityHook, Finalized, TenderbakeNode};
+
+    // node 3 starts late and catches up several levels at once
+    #[test]
+    fn every_level_is_reported_once_in_order() {
+        let round0 = Duration::from_secs(1);
+        let delay = DelayModel::Constant(Duration::from_millis(10));
+        let configs = TenderbakeConfig::new(4, round0, 0);
+        let mut simulator = Simulator::new(configs.map(TenderbakeNode::new), CaseNetwork::new(delay, 0))
+            .with_start_times(vec![Duration::default(), Duration::default(), Duration::default(), round0 * 5]);
+        let mut reported = vec![vec![]; 4];
+        let mut hook = FinalityHook::new(|finalized: Finalized<'_>| {
+            let Finalized { time, node_id, level, block } = finalized;
+            reported[node_id].push((time, level, block.hash()));
+        });
+        simulator.run_until(1_000_000, &mut hook, |time, _| time >= round0 * 12);
+
+        for (node, reported) in simulator.nodes().flatten().zip(reported) {
+            assert!(node.finalized_level() >= 8, "{}", node.finalized_level());
+            let levels = reported.iter().map(|&(_, level, _)| level).collect::<Vec<_>>();
+            assert_eq!(levels, (1..=node.finalized_level()).collect::<Vec<_>>());
+            assert!(reported.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+            let hashes = reported.into_iter().map(|(_, _, hash)| hash).collect::<Vec<_>>();
+            let blocks = node.finalized_blocks().map(|block| block.hash()).collect::<Vec<BlockHash>>();
+            assert_eq!(hashes, blocks);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::{collections::BTreeMap, error::Error, fmt, time::Duration};
+use serde::{Serialize, Deserialize};
+use crate::{Invariant, Violation};
+use super::{
+    blockchain::{StateHash, Transaction},
+    node::TenderbakeNode,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferError {
+    UnknownAccount(u32),
+    BadNonce {
+        expected: u32,
+        found: u32,
+    },
+    InsufficientBalance {
+        balance: u64,
+        amount: u32,
+    },
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransferError::UnknownAccount(account) => write!(f, "no account {}", account),
+            TransferError::BadNonce { expected, found } => {
+                write!(f, "nonce {}, expected {}", found, expected)
+            },
+            TransferError::InsufficientBalance { balance, amount } => {
+                write!(f, "cannot transfer {} out of {}", amount, balance)
+            },
+        }
+    }
+}
+
+impl Error for TransferError {}
+
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+struct Account {
+    balance: u64,
+    nonce: u32,
+}
+
+// account balances, the state every node derives from the finalized blocks
+#[derive(Debug, Clone, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ledger {
+    accounts: BTreeMap<u32, Account>,
+}
+
+impl Ledger {
+    // accounts `0..accounts`, each holding `balance`
+    pub fn new(accounts: u32, balance: u64) -> Self {
+        Ledger {
+            accounts: (0..accounts)
+                .map(|id| (id, Account { balance, nonce: 0 }))
+                .collect(),
+        }
+    }
+
+    pub fn balance(&self, account: u32) -> Option<u64> {
+        self.accounts.get(&account).map(|a| a.balance)
+    }
+
+    // the nonce the next transfer of the account must carry
+    pub fn nonce(&self, account: u32) -> Option<u32> {
+        self.accounts.get(&account).map(|a| a.nonce)
+    }
+
+    pub fn check(&self, transaction: &Transaction) -> Result<(), TransferError> {
+        let receiver = transaction.receiver();
+        if !self.accounts.contains_key(&receiver) {
+            return Err(TransferError::UnknownAccount(receiver));
+        }
+        let sender = self.accounts
+            .get(&transaction.sender())
+            .ok_or_else(|| TransferError::UnknownAccount(transaction.sender()))?;
+        if sender.nonce != transaction.nonce() {
+            return Err(TransferError::BadNonce {
+                expected: sender.nonce,
+                found: transaction.nonce(),
+            });
+        }
+        if sender.balance < u64::from(transaction.amount()) {
+            return Err(TransferError::InsufficientBalance {
+                balance: sender.balance,
+                amount: transaction.amount(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn apply(&mut self, transaction: &Transaction) -> Result<(), TransferError> {
+        self.check(transaction)?;
+        let amount = u64::from(transaction.amount());
+        let sender = self.accounts.get_mut(&transaction.sender()).expect("checked");
+        sender.balance -= amount;
+        sender.nonce += 1;
+        self.accounts.get_mut(&transaction.receiver()).expect("checked").balance += amount;
+        Ok(())
+    }
+
+    // applies all transactions of a block or none of them
+    pub fn apply_all(&mut self, transactions: &[Transaction]) -> Result<(), TransferError> {
+        let mut next = self.clone();
+        for transaction in transactions {
+            next.apply(transaction)?;
+        }
+        *self = next;
+        Ok(())
+    }
+
+    // a transfer that can never be applied, its nonce is already used
+    pub fn is_stale(&self, transaction: &Transaction) -> bool {
+        self.nonce(transaction.sender())
+            .map(|nonce| transaction.nonce() < nonce)
+            .unwrap_or(true)
+    }
+
+    pub fn hash(&self) -> StateHash {
+        let mut bytes = Vec::with_capacity(self.accounts.len() * 16);
+        for (id, account) in &self.accounts {
+            bytes.extend_from_slice(&id.to_be_bytes());
+            bytes.extend_from_slice(&account.balance.to_be_bytes());
+            bytes.extend_from_slice(&account.nonce.to_be_bytes());
+        }
+        StateHash::digest(&bytes)
+    }
+}
+
+// no two nodes reach a different ledger at the same level, unlike
+// `Agreement` it compares the state each node computed by itself
+#[derive(Clone, Default)]
+pub struct StateAgreement {
+    states: Vec<(StateHash, usize)>,
+    checked: Vec<usize>,
+}
+
+impl StateAgreement {
+    pub fn new() -> Self {
+        StateAgreement::default()
+    }
+}
+
+impl Invariant<TenderbakeNode> for StateAgreement {
+    fn check(&mut self, time: Duration, nodes: &[Option<&TenderbakeNode>]) -> Result<(), Violation> {
+        self.checked.resize(nodes.len(), 0);
+        for (node_id, node) in nodes.iter().enumerate() {
+            let node = match node {
+                Some(node) => node,
+                None => continue,
+            };
+            let states = node.state_hashes();
+            for (level, state) in states.iter().enumerate().skip(self.checked[node_id]) {
+                match self.states.get(level) {
+                    None => self.states.push((state.clone(), node_id)),
+                    Some((other, other_id)) if other != state => {
+                        return Err(Violation {
+                            invariant: "state agreement",
+                            time,
+                            description: format!(
+                                "level {}: node {} computed {:?}, node {} computed {:?}",
+                                level, other_id, other, node_id, state,
+                            ),
+                        });
+                    },
+                    Some(_) => (),
+                }
+            }
+            self.checked[node_id] = states.len();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ledger, Transaction, TransferError};
+
+    #[test]
+    fn transfer() {
+        let mut ledger = Ledger::new(2, 100);
+        ledger.apply(&Transaction::new(0, 1, 30, 0)).unwrap();
+        assert_eq!((ledger.balance(0), ledger.balance(1)), (Some(70), Some(130)));
+        assert_eq!((ledger.nonce(0), ledger.nonce(1)), (Some(1), Some(0)));
+        // the whole balance may go
+        ledger.apply(&Transaction::new(0, 1, 70, 1)).unwrap();
+        assert_eq!(ledger.balance(0), Some(0));
+    }
+
+    #[test]
+    fn rejected_transfer_changes_nothing() {
+        let mut ledger = Ledger::new(2, 100);
+        ledger.apply(&Transaction::new(0, 1, 10, 0)).unwrap();
+        let before = ledger.clone();
+        let cases = vec![
+            (Transaction::new(0, 1, 10, 0), TransferError::BadNonce { expected: 1, found: 0 }),
+            (Transaction::new(0, 1, 10, 2), TransferError::BadNonce { expected: 1, found: 2 }),
+            (Transaction::new(0, 1, 91, 1), TransferError::InsufficientBalance { balance: 90, amount: 91 }),
+            (Transaction::new(2, 1, 10, 0), TransferError::UnknownAccount(2)),
+            (Transaction::new(0, 2, 10, 1), TransferError::UnknownAccount(2)),
+        ];
+        for (transaction, error) in cases {
+            assert_eq!(ledger.check(&transaction), Err(error.clone()));
+            assert_eq!(ledger.apply(&transaction), Err(error));
+            assert_eq!(ledger, before);
+        }
+    }
+
+    #[test]
+    fn apply_all_is_all_or_nothing() {
+        let mut ledger = Ledger::new(2, 100);
+        let block = [Transaction::new(0, 1, 60, 0), Transaction::new(0, 1, 60, 1)];
+        assert_eq!(
+            ledger.apply_all(&block),
+            Err(TransferError::InsufficientBalance { balance: 40, amount: 60 }),
+        );
+        assert_eq!(ledger, Ledger::new(2, 100));
+
+        // a later transfer may spend what an earlier one of the block brought
+        let block = [Transaction::new(0, 1, 60, 0), Transaction::new(1, 0, 160, 0)];
+        ledger.apply_all(&block).unwrap();
+        assert_eq!((ledger.balance(0), ledger.balance(1)), (Some(200), Some(0)));
+        assert_ne!(ledger.hash(), Ledger::new(2, 100).hash());
+    }
+
+    #[test]
+    fn stale() {
+        let mut ledger = Ledger::new(2, 100);
+        ledger.apply(&Transaction::new(0, 1, 10, 0)).unwrap();
+        assert!(ledger.is_stale(&Transaction::new(0, 1, 10, 0)));
+        assert!(!ledger.is_stale(&Transaction::new(0, 1, 10, 1)));
+        // not applicable yet, but it may be later
+        assert!(!ledger.is_stale(&Transaction::new(0, 1, 10, 5)));
+        assert!(!ledger.is_stale(&Transaction::new(0, 1, 1_000, 1)));
+        assert!(ledger.is_stale(&Transaction::new(7, 1, 10, 0)));
+    }
+}
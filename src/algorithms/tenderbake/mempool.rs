@@ -0,0 +1,115 @@
+use std::{collections::BTreeSet, time::Duration};
+use serde::{Serialize, Deserialize};
+use crate::MessageSize;
+use super::blockchain::Transaction;
+
+// pending transactions in the order of arrival
+#[derive(Clone, Default, Hash, Serialize, Deserialize)]
+pub struct Mempool {
+    pending: Vec<(Transaction, Duration)>,
+    // finalized transactions, so late gossip does not bring them back
+    included: BTreeSet<Transaction>,
+}
+
+impl Mempool {
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    // returns false if the transaction is already known
+    pub fn add(&mut self, transaction: Transaction, time: Duration) -> bool {
+        let known = self.included.contains(&transaction)
+            || self.pending.iter().any(|(t, _)| *t == transaction);
+        if !known {
+            self.pending.push((transaction, time));
+        }
+        !known
+    }
+
+    // the oldest accepted transactions that fit into `max_size` bytes
+    pub fn select<F>(&self, max_size: usize, mut accept: F) -> Vec<Transaction>
+    where
+        F: FnMut(&Transaction) -> bool,
+    {
+        let mut size = 0;
+        let mut selected = vec![];
+        for (transaction, _) in &self.pending {
+            if size + transaction.size() > max_size {
+                break;
+            }
+            if accept(transaction) {
+                size += transaction.size();
+                selected.push(transaction.clone());
+            }
+        }
+        selected
+    }
+
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&Transaction) -> bool,
+    {
+        self.pending.retain(|(transaction, _)| keep(transaction));
+    }
+
+    // removes the finalized transactions, returns the time each of them was
+    // received if it passed through this mempool
+    pub fn remove(&mut self, transactions: &[Transaction]) -> Vec<Duration> {
+        let mut received = vec![];
+        for transaction in transactions {
+            if self.included.insert(transaction.clone()) {
+                if let Some(index) = self.pending.iter().position(|(t, _)| t == transaction) {
+                    received.push(self.pending.remove(index).1);
+                }
+            }
+        }
+        received
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::MessageSize;
+    use super::{Mempool, Transaction};
+
+    fn mempool(transactions: &[Transaction]) -> Mempool {
+        let mut mempool = Mempool::default();
+        for (i, transaction) in transactions.iter().enumerate() {
+            assert!(mempool.add(transaction.clone(), Duration::from_secs(i as u64)));
+        }
+        mempool
+    }
+
+    #[test]
+    fn select_fills_the_block_with_the_oldest() {
+        let transactions = (0..5).map(|nonce| Transaction::new(0, 1, 10, nonce)).collect::<Vec<_>>();
+        let size = transactions[0].size();
+        let mempool = mempool(&transactions);
+        assert_eq!(mempool.select(size * 3, |_| true), &transactions[..3]);
+        assert_eq!(mempool.select(size * 3 - 1, |_| true), &transactions[..2]);
+        assert!(mempool.select(size - 1, |_| true).is_empty());
+        // a rejected transaction takes no room
+        let odd = mempool.select(size * 2, |t| t.nonce() % 2 == 1);
+        assert_eq!(odd, [transactions[1].clone(), transactions[3].clone()]);
+    }
+
+    #[test]
+    fn finalized_transaction_is_not_added_again() {
+        let transactions = (0..3).map(|nonce| Transaction::new(0, 1, 10, nonce)).collect::<Vec<_>>();
+        let mut mempool = mempool(&transactions);
+        assert!(!mempool.add(transactions[0].clone(), Duration::from_secs(9)));
+
+        // the time each was received, none for a transaction seen only in a block
+        let other = Transaction::new(2, 1, 10, 0);
+        let received = mempool.remove(&[transactions[1].clone(), other.clone()]);
+        assert_eq!(received, [Duration::from_secs(1)]);
+        assert_eq!(mempool.len(), 2);
+        assert!(!mempool.add(transactions[1].clone(), Duration::from_secs(9)));
+        assert!(!mempool.add(other, Duration::from_secs(9)));
+    }
+}
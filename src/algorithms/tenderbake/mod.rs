@@ -1,8 +1,29 @@
 pub mod blockchain;
 
+mod block_tree;
+pub use self::block_tree::BlockTree;
+
 mod config;
 pub use self::config::TenderbakeConfig;
 
-#[allow(dead_code)]
+mod finality;
+pub use self::finality::{FinalityHook, Finalized};
+
+mod ledger;
+pub use self::ledger::{Ledger, TransferError, StateAgreement};
+
+mod mempool;
+pub use self::mempool::Mempool;
+
 mod node;
 pub use self::node::TenderbakeNode;
+
+// the message carries the signature of its sender, there is no separate
+// `Signed` wrapper on the wire; levels and rounds are `u64` as before and
+// the encoding narrows them to 32 bits, as Tezos does
+pub type TenderbakeMessage = node::Msg;
+pub type TenderbakeDecodeError = blockchain::DecodeError;
+pub type TenderbakeTransaction = blockchain::Transaction;
+pub type TenderbakeBlock = blockchain::Block;
+pub type TenderbakeBlockHash = blockchain::BlockHash;
+pub type TenderbakeRound = blockchain::Round;
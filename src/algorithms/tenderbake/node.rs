@@ -1,23 +1,151 @@
 use std::time::Duration;
-use super::{blockchain, config::TenderbakeConfig};
-use crate::{Node, NodeEvent, Effect};
+use serde::{Serialize, Deserialize};
+use tezos_encoding::{encoding::HasEncoding, nom::NomReader};
+use tezos_messages::p2p::binary_message::{BinaryRead, BinaryWrite};
+use super::{blockchain, block_tree::BlockTree, config::TenderbakeConfig, ledger::Ledger, mempool::Mempool};
+use crate::{Node, NodeEvent, Effect, MessageSize, MessageKind, Decisions, SyncTimes};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+#[encoding(tags = "u8")]
 enum Payload {
-    Propose(blockchain::Chain),
-    Preendorse(blockchain::Preendorsement),
-    Endorse(blockchain::Endorsement, Vec<blockchain::Preendorsement>),
-    Preendorsements(blockchain::Block, Vec<blockchain::Preendorsement>),
+    #[encoding(tag = 0x00)]
+    Propose(blockchain::Block),
+    // a vote is sent as the message, the header carries its level, round
+    // and signature
+    #[encoding(tag = 0x01)]
+    Preendorse(blockchain::BlockHash),
+    #[encoding(tag = 0x02)]
+    Endorse(Endorse),
+    #[encoding(tag = 0x03)]
+    Preendorsements(Preendorsements),
+    #[encoding(tag = 0x04)]
+    Transaction(blockchain::Transaction),
+    #[encoding(tag = 0x05)]
+    Request(Request),
+    #[encoding(tag = 0x06)]
+    Blocks(Blocks),
 }
 
-#[derive(Debug, Clone)]
+// the endorsement with the preendorsement quorum the endorser has seen
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+struct Endorse {
+    block_hash: blockchain::BlockHash,
+    #[encoding(dynamic, list)]
+    pqc: Vec<blockchain::Preendorsement>,
+}
+
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+struct Preendorsements {
+    block: blockchain::Block,
+    #[encoding(dynamic, list)]
+    pqc: Vec<blockchain::Preendorsement>,
+}
+
+// the blocks above the level the requester has decided: the block with the
+// hash and its ancestors, the requester has got its successor, or without
+// a hash the decided blocks up to the level, the requester is behind
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+struct Request {
+    hash: Option<blockchain::BlockHash>,
+    #[encoding(builtin = "Uint32")]
+    level: u32,
+    #[encoding(builtin = "Uint32")]
+    decided: u32,
+}
+
+// in ascending order, with the endorsement quorum of the last one if it is
+// decided, a quorum is never empty
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
+struct Blocks {
+    #[encoding(dynamic, list)]
+    blocks: Vec<blockchain::Block>,
+    #[encoding(dynamic, list)]
+    eqc: Vec<blockchain::Endorsement>,
+}
+
+// a message as it goes over the wire, signed by the sender
+#[derive(Debug, Clone, Hash, HasEncoding, NomReader, Serialize, Deserialize)]
 pub struct Msg {
-    level: u64,
-    round_id: u64,
+    #[encoding(builtin = "Uint32")]
+    level: u32,
+    #[encoding(builtin = "Uint32")]
+    round_id: u32,
     previous_block_hash: Option<blockchain::BlockHash>,
     payload: Payload,
+    signature: blockchain::Signature,
 }
 
+impl Msg {
+    // the Tezos binary encoding
+    pub fn encode(&self) -> Vec<u8> {
+        self.as_bytes().unwrap()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, blockchain::DecodeError> {
+        let msg = Msg::from_bytes(bytes)?;
+        match &msg.payload {
+            Payload::Propose(block) | Payload::Preendorsements(Preendorsements { block, .. }) => block.check()?,
+            Payload::Blocks(Blocks { blocks, .. }) => blocks.iter().try_for_each(blockchain::Block::check)?,
+            _ => (),
+        }
+        Ok(msg)
+    }
+
+    pub fn level(&self) -> u64 {
+        u64::from(self.level)
+    }
+
+    pub fn round_id(&self) -> u64 {
+        u64::from(self.round_id)
+    }
+
+    pub fn signer(&self) -> usize {
+        self.signature.signer()
+    }
+
+    pub fn is_proposal(&self) -> bool {
+        matches!(self.payload, Payload::Propose(_))
+    }
+
+    pub fn is_preendorsement(&self) -> bool {
+        matches!(self.payload, Payload::Preendorse(_))
+    }
+
+    pub fn is_endorsement(&self) -> bool {
+        matches!(self.payload, Payload::Endorse(_))
+    }
+}
+
+impl MessageSize for Msg {
+    fn size(&self) -> usize {
+        self.as_bytes().unwrap().len()
+    }
+}
+
+impl MessageKind for Msg {
+    type Proposal = blockchain::BlockHash;
+
+    fn kind(&self) -> &'static str {
+        match self.payload {
+            Payload::Propose(_) => "propose",
+            Payload::Preendorse(_) => "preendorse",
+            Payload::Endorse(_) => "endorse",
+            Payload::Preendorsements(_) => "preendorsements",
+            Payload::Transaction(_) => "transaction",
+            Payload::Request(_) => "request",
+            Payload::Blocks(_) => "blocks",
+        }
+    }
+
+    fn proposal(&self) -> Option<(Self::Proposal, u64)> {
+        match self.payload {
+            Payload::Propose(ref block) => Some((block.hash(), u64::from(self.round_id))),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Hash, Serialize, Deserialize)]
 enum ProposalState {
     NoProposal,
     CollectingPreendorsements {
@@ -29,87 +157,1130 @@ enum ProposalState {
     },
 }
 
+type Effects = Vec<Effect<Msg>>;
+
+#[derive(Clone, Hash, Serialize, Deserialize)]
 pub struct TenderbakeNode {
     config: TenderbakeConfig,
-    chain: blockchain::Chain,
+    // every known block, and the branch the fork choice has selected
+    tree: BlockTree,
+    branch: Vec<blockchain::BlockHash>,
+    // the endorsement quorum of the head, once it is known the head is
+    // decided and the node works on the next level
+    head_eqc: Option<Vec<blockchain::Endorsement>>,
+    // local time when round 0 of the current level starts
+    level_start: Duration,
+    round: u64,
+    // the level and round the node has entered
+    entered: (u32, u64),
     proposal_state: ProposalState,
     endorsable: Option<blockchain::Round>,
     locked: Option<blockchain::Round>,
+    // votes that arrived before the proposal they vote for, and proposals
+    // of a round the node has not entered yet
+    early: Vec<(usize, Msg)>,
+    // proposals whose ancestors are requested from the proposer
+    orphans: Vec<(usize, Msg)>,
+    // when the node found out it is behind, when it last asked for the
+    // missed levels and how many levels it must decide to catch up, and
+    // how long the catching up took
+    sync: Option<(Duration, Duration, usize)>,
+    sync_times: Vec<Duration>,
+    mempool: Mempool,
+    transaction_latencies: Vec<Duration>,
+    // the state after the decided blocks, and its hash after each of them
+    ledger: Ledger,
+    state_hashes: Vec<blockchain::StateHash>,
 }
 
 impl TenderbakeNode {
     pub fn new(config: TenderbakeConfig) -> Self {
+        let ledger = Ledger::new(config.accounts, config.initial_balance);
         TenderbakeNode {
             config,
-            chain: blockchain::Chain::default(),
+            tree: BlockTree::default(),
+            branch: vec![],
+            // there is nothing to agree on below the first level
+            head_eqc: Some(vec![]),
+            level_start: Duration::default(),
+            round: 0,
+            entered: (0, 0),
             proposal_state: ProposalState::NoProposal,
             endorsable: None,
             locked: None,
+            early: vec![],
+            orphans: vec![],
+            sync: None,
+            sync_times: vec![],
+            mempool: Mempool::default(),
+            transaction_latencies: vec![],
+            ledger,
+            state_hashes: vec![],
+        }
+    }
+
+    pub fn block_tree(&self) -> &BlockTree {
+        &self.tree
+    }
+
+    pub fn mempool(&self) -> &Mempool {
+        &self.mempool
+    }
+
+    // from the moment the node learned about a transaction until it
+    // decided the block that includes it, by the local clock
+    pub fn transaction_latencies(&self) -> &[Duration] {
+        &self.transaction_latencies
+    }
+
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    // the hash of the ledger after each decided level, computed locally
+    pub fn state_hashes(&self) -> &[blockchain::StateHash] {
+        &self.state_hashes
+    }
+
+    // the level the node is voting on
+    pub fn level(&self) -> u64 {
+        u64::from(self.current_level())
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    // the width of the wire format
+    fn current_level(&self) -> u32 {
+        self.branch.len() as u32 + if self.head_eqc.is_some() { 1 } else { 0 }
+    }
+
+    // the head of the branch the fork choice has selected, decided or not
+    pub fn head(&self) -> Option<&blockchain::Block> {
+        self.branch.last().and_then(|hash| self.tree.get(hash))
+    }
+
+    pub fn locked(&self) -> Option<&blockchain::Round> {
+        self.locked.as_ref()
+    }
+
+    pub fn endorsable(&self) -> Option<&blockchain::Round> {
+        self.endorsable.as_ref()
+    }
+
+    // the level of the last decided block, zero before the first decision
+    pub fn finalized_level(&self) -> u32 {
+        self.decided_levels() as u32
+    }
+
+    pub fn finalized_block(&self, level: u32) -> Option<&blockchain::Block> {
+        let index = (level as usize).checked_sub(1)?;
+        self.branch[..self.decided_levels()]
+            .get(index)
+            .and_then(|hash| self.tree.get(hash))
+    }
+
+    pub fn finalized_blocks(&self) -> impl Iterator<Item = &blockchain::Block> {
+        self.branch[..self.decided_levels()]
+            .iter()
+            .map(move |hash| self.tree.get(hash).expect("the branch is in the tree"))
+    }
+
+    fn round_duration(&self, round: u64) -> Duration {
+        self.config.round0_duration * (round as u32 + 1)
+    }
+
+    // from the start of the level to the start of the round
+    fn round_offset(&self, round: u64) -> Duration {
+        self.config.round0_duration * (round * (round + 1) / 2) as u32
+    }
+
+    fn round_start(&self, round: u64) -> Duration {
+        self.level_start + self.round_offset(round)
+    }
+
+    fn round_at(&self, time: Duration) -> u64 {
+        let mut round = 0;
+        while self.round_start(round + 1) <= time {
+            round += 1;
+        }
+        round
+    }
+
+    // a level starts when the round of the block decided below it is over,
+    // while the head is undecided its proposer tells when its level started
+    fn update_level_start(&mut self) {
+        self.level_start = match (self.head(), &self.head_eqc) {
+            (None, _) => Duration::default(),
+            (Some(head), Some(_)) => head.timestamp() + self.round_duration(head.round()),
+            (Some(head), None) => head.timestamp().saturating_sub(self.round_offset(head.round())),
+        };
+    }
+
+    fn message(&self, payload: Payload) -> Msg {
+        let previous_block_hash = match self.head_eqc {
+            Some(_) => self.branch.last().cloned(),
+            None => self.head().and_then(|head| head.contents().predecessor_hash().cloned()),
+        };
+        Msg {
+            level: self.current_level(),
+            round_id: blockchain::wire_round(self.round),
+            previous_block_hash,
+            payload,
+            signature: blockchain::Signature::sign(self.config.id),
         }
     }
+
+    fn broadcast(&self, payload: Payload) -> Effect<Msg> {
+        Effect::Broadcast(self.message(payload))
+    }
+
+    // a preendorsement quorum of the current round
+    fn is_quorum_for(&self, contents: &blockchain::BlockContents, pqc: &[blockchain::Preendorsement]) -> bool {
+        let pqc = blockchain::QuorumCertificate::new(pqc.to_vec());
+        pqc.quorum_round(&self.config, contents) == Some(self.round)
+    }
+
+    // enters the round the clock is in, proposes if it is the proposer,
+    // and schedules the next round
+    fn tick(&mut self, time: Duration) -> Effects {
+        if time < self.level_start {
+            return vec![Effect::SetWakeUpTime(self.level_start)];
+        }
+        let round = self.round_at(time);
+        let level = self.current_level();
+        let mut effects = vec![];
+        if self.entered < (level, round) {
+            if self.entered.0 == level {
+                effects.extend(self.share_endorsable());
+            }
+            self.enter(level, round);
+            if is_proposer(self.config.id, self.config.total_nodes, level, round) {
+                effects.extend(self.propose(time));
+            }
+            // a proposer whose clock runs ahead may have proposed already
+            let (ready, later) = std::mem::take(&mut self.early)
+                .into_iter()
+                .partition::<Vec<_>, _>(|(_, msg)| msg.is_proposal() && (msg.level, msg.round_id()) == (level, round));
+            self.early = later;
+            for (signer_id, msg) in ready {
+                effects.extend(self.on_msg(time, signer_id, msg));
+            }
+        }
+        effects.push(Effect::SetWakeUpTime(self.round_start(self.round + 1)));
+        effects
+    }
+
+    // the round is over without a decision, the nodes that missed the
+    // preendorsement quorum learn the block must be proposed again
+    fn share_endorsable(&self) -> Option<Effect<Msg>> {
+        let endorsable = self.endorsable.as_ref()?;
+        let block = self.tree.get(&endorsable.block_contents().hash())?.clone();
+        let pqc = endorsable.quorum_certificate().to_vec();
+        Some(self.broadcast(Payload::Preendorsements(Preendorsements { block, pqc })))
+    }
+
+    fn enter(&mut self, level: u32, round: u64) {
+        self.round = round;
+        self.entered = (level, round);
+        self.proposal_state = ProposalState::NoProposal;
+        self.early.retain(|(_, msg)| (msg.level, msg.round_id()) >= (level, round));
+        self.orphans.retain(|(_, msg)| (msg.level, msg.round_id()) >= (level, round));
+    }
+
+    fn propose(&mut self, time: Duration) -> Effects {
+        let (level, round) = (self.current_level(), self.round);
+        let (predecessor_hash, predecessor_eqc) = match self.head_eqc {
+            Some(ref eqc) => (self.branch.last().cloned(), eqc.clone()),
+            None => {
+                let head = self.head().expect("undecided head");
+                (head.contents().predecessor_hash().cloned(), head.predecessor_eqc().to_vec())
+            },
+        };
+        // a block that may have been decided by someone must be proposed again
+        let (contents, previously_proposed) = match self.endorsable {
+            Some(ref endorsable) => (
+                endorsable.block_contents().clone(),
+                Some(blockchain::QuorumCertificate::new(endorsable.quorum_certificate().to_vec())),
+            ),
+            None => {
+                // the ledger is at the predecessor, it is decided
+                let mut ledger = self.ledger.clone();
+                let transactions = self.mempool
+                    .select(self.config.max_block_size, |t| ledger.apply(t).is_ok());
+                let contents = blockchain::BlockContents::new(
+                    transactions,
+                    level,
+                    predecessor_hash,
+                    ledger.hash(),
+                );
+                (contents, None)
+            },
+        };
+        let timestamp = self.round_start(round);
+        let block = blockchain::Block::new(contents, round, timestamp, predecessor_eqc, previously_proposed);
+
+        let mut effects = vec![self.broadcast(Payload::Propose(block.clone()))];
+        effects.extend(self.on_block(time, self.config.id, level, round, block));
+        effects
+    }
+
+    fn request(&self, signer_id: usize, hash: Option<blockchain::BlockHash>, level: u32) -> Effect<Msg> {
+        let decided = self.state_hashes.len() as u32;
+        let request = Request { hash, level, decided };
+        Effect::Send(signer_id, self.message(Payload::Request(request)))
+    }
+
+    // a message of a later level than the next one means the sender has
+    // decided levels this node has missed, asks the sender for them unless
+    // a request is already in flight
+    fn sync(&mut self, time: Duration, signer_id: usize, level: u32) -> Option<Effect<Msg>> {
+        if level <= self.current_level() + 1 {
+            return None;
+        }
+        let target = (level - 1) as usize;
+        match self.sync {
+            Some((_, last_request, _)) if time < last_request + self.round_duration(self.round) => return None,
+            Some((started, _, t)) => self.sync = Some((started, time, t.max(target))),
+            None => self.sync = Some((time, time, target)),
+        }
+        Some(self.request(signer_id, None, target as u32))
+    }
+
+    // the endorsement quorum of a block of the branch, if it is decided
+    fn quorum_of(&self, hash: &blockchain::BlockHash) -> Option<Vec<blockchain::Endorsement>> {
+        let level = self.tree.get(hash)?.contents().level() as usize;
+        if self.branch.get(level - 1) != Some(hash) {
+            return None;
+        }
+        match self.branch.get(level) {
+            Some(next) => self.tree.get(next).map(|block| block.predecessor_eqc().to_vec()),
+            None => self.head_eqc.clone(),
+        }
+    }
+
+    fn on_request(&self, signer_id: usize, request: Request) -> Effects {
+        let last = match request.hash {
+            Some(hash) => hash,
+            None => match self.decided_levels().min(request.level as usize).checked_sub(1) {
+                Some(index) => self.branch[index].clone(),
+                None => return vec![],
+            },
+        };
+        if !self.tree.contains(&last) {
+            return vec![];
+        }
+        let blocks = self.tree.segment(&last, request.decided);
+        if blocks.is_empty() {
+            return vec![];
+        }
+        let eqc = self.quorum_of(&last).unwrap_or_default();
+        vec![Effect::Send(signer_id, self.message(Payload::Blocks(Blocks { blocks, eqc })))]
+    }
+
+    // the block joins the tree as a branch, then it is considered as a proposal
+    fn on_block(
+        &mut self,
+        time: Duration,
+        signer_id: usize,
+        level: u32,
+        round_id: u64,
+        block: blockchain::Block,
+    ) -> Effects {
+        if !self.tree.is_valid_successor(&block, &self.config) {
+            return vec![];
+        }
+        self.tree.insert(block.clone());
+        self.on_proposal(time, signer_id, level, round_id, block)
+    }
+
+    fn on_proposal(
+        &mut self,
+        time: Duration,
+        signer_id: usize,
+        level: u32,
+        round_id: u64,
+        head: blockchain::Block,
+    ) -> Effects {
+        let is_proposer_valid = is_proposer(signer_id, self.config.total_nodes, level, round_id);
+        let is_current = if level == self.current_level() {
+            self.entered == (level, round_id) && matches!(self.proposal_state, ProposalState::NoProposal)
+        } else {
+            // the node has missed the decision of its level
+            level > self.current_level()
+        };
+        // a locked node accepts only the locked block, or a block justified
+        // by a preendorsement quorum of the locked round or later
+        let is_lock_respected = match self.locked {
+            None => true,
+            Some(ref locked) => {
+                level > self.current_level()
+                    || locked.block_contents().hash() == head.hash()
+                    || head
+                        .previously_proposed()
+                        .and_then(blockchain::QuorumCertificate::round)
+                        .map(|round| round >= locked.round_id())
+                        .unwrap_or(false)
+            },
+        };
+        let endorsable = if level == self.current_level() { self.endorsable.as_ref() } else { None };
+        let candidate = match head.contents().predecessor_hash().and_then(|hash| self.tree.get(hash)) {
+            Some(parent) => blockchain::Chain::from(vec![parent.clone(), head.clone()]),
+            None => blockchain::Chain::from(vec![head.clone()]),
+        };
+        let ok = is_proposer_valid
+            && is_current
+            && is_lock_respected
+            && head.contents().level() == level
+            && head.round() == round_id
+            && candidate.better_chain(&self.tree.tip(self.branch.last()), endorsable)
+            && self.execute(&head).is_some();
+        if !ok {
+            return vec![];
+        }
+
+        let head_hash = head.hash();
+        let new_level = level > self.current_level();
+        self.set_head(head);
+        self.head_eqc = None;
+        if new_level {
+            self.endorsable = None;
+            self.locked = None;
+            self.update_level_start();
+        }
+        self.finalize(time, self.branch.len() - 1);
+        self.enter(level, round_id);
+        self.proposal_state = ProposalState::CollectingPreendorsements { acc: vec![] };
+
+        let preendorsement = blockchain::Preendorsement::new(self.config.id, level, round_id, head_hash.clone());
+        let mut effects = vec![self.broadcast(Payload::Preendorse(head_hash))];
+        effects.extend(self.on_preendorsement(time, self.config.id, preendorsement));
+        for (signer_id, msg) in std::mem::take(&mut self.early) {
+            effects.extend(self.on_msg(time, signer_id, msg));
+        }
+        effects
+    }
+
+    fn on_preendorsement(
+        &mut self,
+        time: Duration,
+        signer_id: usize,
+        preendorsement: blockchain::Preendorsement,
+    ) -> Effects {
+        let head_hash = self.branch.last().cloned();
+        let quorum_size = self.config.quorum_size;
+        let pqc = match self.proposal_state {
+            ProposalState::CollectingPreendorsements { ref mut acc } => {
+                let is_new = acc.iter().all(|p| p.signer() != signer_id);
+                if Some(preendorsement.block_hash()) == head_hash.as_ref() && is_new {
+                    acc.push(preendorsement);
+                }
+                if acc.len() < quorum_size {
+                    return vec![];
+                }
+                acc.clone()
+            },
+            _ => return vec![],
+        };
+        self.on_pqc(time, pqc)
+    }
+
+    fn on_pqc(&mut self, time: Duration, pqc: Vec<blockchain::Preendorsement>) -> Effects {
+        let head = self.head().expect("preendorsed head").clone();
+        let round = blockchain::Round::new(self.round, head.contents().clone(), pqc.clone());
+        self.locked = Some(round.clone());
+        self.endorsable = Some(round);
+        self.proposal_state = ProposalState::CollectingEndorsements {
+            pqc: pqc.clone(),
+            acc: vec![],
+        };
+
+        let level = self.current_level();
+        let endorsement = blockchain::Endorsement::new(self.config.id, level, self.round, head.hash());
+        let endorse = Endorse {
+            block_hash: head.hash(),
+            pqc,
+        };
+        let mut effects = vec![self.broadcast(Payload::Endorse(endorse))];
+        effects.extend(self.on_endorsement(time, self.config.id, endorsement, vec![]));
+        effects
+    }
+
+    fn on_endorsement(
+        &mut self,
+        time: Duration,
+        signer_id: usize,
+        endorsement: blockchain::Endorsement,
+        pqc: Vec<blockchain::Preendorsement>,
+    ) -> Effects {
+        let head = match self.head() {
+            Some(head) => head,
+            None => return vec![],
+        };
+        if endorsement.block_hash() != &head.hash() {
+            return vec![];
+        }
+        // the preendorsement quorum the endorser has seen is as good as our own
+        if let ProposalState::CollectingPreendorsements { .. } = self.proposal_state {
+            if !self.is_quorum_for(head.contents(), &pqc) {
+                return vec![];
+            }
+            let mut effects = self.on_pqc(time, pqc);
+            effects.extend(self.on_endorsement(time, signer_id, endorsement, vec![]));
+            return effects;
+        }
+
+        let quorum_size = self.config.quorum_size;
+        let eqc = match self.proposal_state {
+            ProposalState::CollectingEndorsements { ref mut acc, .. } => {
+                if acc.iter().all(|e| e.signer() != signer_id) {
+                    acc.push(endorsement);
+                }
+                if acc.len() < quorum_size {
+                    return vec![];
+                }
+                acc.clone()
+            },
+            _ => return vec![],
+        };
+        self.head_eqc = Some(eqc);
+        self.endorsable = None;
+        self.locked = None;
+        self.proposal_state = ProposalState::NoProposal;
+        self.update_level_start();
+        self.finalize(time, self.branch.len());
+        vec![]
+    }
+
+    fn on_preendorsements(&mut self, block: blockchain::Block, pqc: Vec<blockchain::Preendorsement>) {
+        let qc = blockchain::QuorumCertificate::new(pqc.clone());
+        let round_id = match qc.quorum_round(&self.config, block.contents()) {
+            Some(round_id) => round_id,
+            None => return,
+        };
+        let higher = self.endorsable
+            .as_ref()
+            .map(|endorsable| round_id > endorsable.round_id())
+            .unwrap_or(true);
+        if higher {
+            self.endorsable = Some(blockchain::Round::new(round_id, block.contents().clone(), pqc));
+        }
+    }
+
+    // the fork choice moved to the branch of the block
+    fn set_head(&mut self, block: blockchain::Block) {
+        let level = block.contents().level() as usize;
+        let hash = block.hash();
+        self.tree.replace(block);
+        let branch = &self.branch;
+        let mut tail = self.tree
+            .ancestors(Some(&hash))
+            .take_while(|&(hash, block)| branch.get(block.contents().level() as usize - 1) != Some(hash))
+            .map(|(hash, _)| hash.clone())
+            .collect::<Vec<_>>();
+        tail.reverse();
+        self.branch.truncate(level - tail.len());
+        self.branch.extend(tail);
+    }
+
+    // the ledger after the block, none if some undecided block of its branch
+    // does not apply or claims a different state
+    fn execute(&self, block: &blockchain::Block) -> Option<Ledger> {
+        let applied = self.state_hashes.len();
+        let mut blocks = self.tree
+            .ancestors(block.contents().predecessor_hash())
+            .map(|(_, block)| block)
+            .take_while(|block| block.contents().level() as usize > applied)
+            .collect::<Vec<_>>();
+        blocks.reverse();
+        blocks.push(block);
+        // the branch must grow out of the decided blocks
+        let root = blocks[0].contents();
+        let decided = applied.checked_sub(1).map(|i| &self.branch[i]);
+        if root.level() as usize != applied + 1 || root.predecessor_hash() != decided {
+            return None;
+        }
+        let mut ledger = self.ledger.clone();
+        for block in blocks {
+            ledger.apply_all(block.contents().transactions()).ok()?;
+            if ledger.hash() != *block.contents().state_hash() {
+                return None;
+            }
+        }
+        Some(ledger)
+    }
+
+    // applies the first `decided` blocks, their transactions leave the mempool
+    fn finalize(&mut self, time: Duration, decided: usize) {
+        for hash in &self.branch[self.state_hashes.len()..decided] {
+            let block = self.tree.get(hash).expect("the branch is in the tree");
+            let transactions = block.contents().transactions();
+            self.ledger
+                .apply_all(transactions)
+                .expect("the block is executed before it is accepted");
+            self.state_hashes.push(self.ledger.hash());
+            let received = self.mempool.remove(transactions);
+            self.transaction_latencies
+                .extend(received.into_iter().map(|received| time.saturating_sub(received)));
+        }
+        let ledger = &self.ledger;
+        self.mempool.retain(|transaction| !ledger.is_stale(transaction));
+        if let Some((started, _, target)) = self.sync {
+            if self.state_hashes.len() >= target {
+                self.sync_times.push(time.saturating_sub(started));
+                self.sync = None;
+            }
+        }
+    }
+
+    // the blocks are decided by others, the node rejoins at the next level
+    fn adopt(&mut self, time: Duration, head: blockchain::Block, eqc: Vec<blockchain::Endorsement>) {
+        let is_new = head.contents().level() >= self.current_level();
+        if !is_new || !head.is_eqc_valid(&self.config, &eqc) || self.execute(&head).is_none() {
+            return;
+        }
+        self.set_head(head);
+        self.head_eqc = Some(eqc);
+        self.endorsable = None;
+        self.locked = None;
+        self.proposal_state = ProposalState::NoProposal;
+        self.update_level_start();
+        self.finalize(time, self.branch.len());
+    }
+
+    // the reply to a request, the blocks must extend the tree and carry
+    // the endorsement quorums of each other
+    fn on_blocks(
+        &mut self,
+        time: Duration,
+        blocks: Vec<blockchain::Block>,
+        eqc: Vec<blockchain::Endorsement>,
+    ) -> Effects {
+        let first = match blocks.first() {
+            Some(first) if self.tree.is_connected(first) => first,
+            _ => return vec![],
+        };
+        let parent = first.contents().predecessor_hash().and_then(|hash| self.tree.get(hash));
+        let chain = parent.into_iter().chain(blocks.iter()).cloned().collect::<Vec<_>>();
+        let is_consecutive = chain
+            .windows(2)
+            .all(|pair| pair[0].contents().level() + 1 == pair[1].contents().level());
+        let is_valid = is_consecutive
+            && blocks.iter().all(|block| block.is_pqc_valid(&self.config))
+            && blockchain::Chain::from(chain).valid_chain(&self.config);
+        if !is_valid {
+            return vec![];
+        }
+        let last = blocks.last().cloned();
+        for block in blocks {
+            self.tree.insert(block);
+        }
+
+        // the proposals waiting for these blocks go first, they may move the
+        // node further than the decided blocks
+        let tree = &self.tree;
+        let (ready, waiting) = std::mem::take(&mut self.orphans)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, msg)| match msg.payload {
+                Payload::Propose(ref block) => tree.is_connected(block),
+                _ => true,
+            });
+        self.orphans = waiting;
+        let mut effects = vec![];
+        for (signer_id, msg) in ready {
+            effects.extend(self.on_msg(time, signer_id, msg));
+        }
+        match last {
+            Some(last) if !eqc.is_empty() => self.adopt(time, last, eqc),
+            _ => (),
+        }
+        effects
+    }
+
+    fn on_msg(&mut self, time: Duration, signer_id: usize, msg: Msg) -> Effects {
+        let mut effects = self.sync(time, signer_id, msg.level).into_iter().collect::<Vec<_>>();
+        effects.extend(self.on_payload(time, signer_id, msg));
+        effects
+    }
+
+    fn on_payload(&mut self, time: Duration, signer_id: usize, msg: Msg) -> Effects {
+        let current = (self.current_level(), self.round);
+        let waiting = matches!(self.proposal_state, ProposalState::NoProposal);
+        match msg.payload {
+            Payload::Propose(ref block) if msg.level >= current.0 && !self.tree.is_connected(block) => {
+                let predecessor = block.contents().predecessor_hash().cloned();
+                let request = predecessor.map(|hash| self.request(signer_id, Some(hash), 0));
+                self.orphans.push((signer_id, msg));
+                request.into_iter().collect()
+            },
+            Payload::Propose(_) if msg.level == current.0 && msg.round_id() > current.1 => {
+                self.early.push((signer_id, msg));
+                vec![]
+            },
+            Payload::Propose(block) => self.on_block(time, signer_id, msg.level, u64::from(msg.round_id), block),
+            Payload::Preendorse(_) | Payload::Endorse(_) if (msg.level, msg.round_id()) < current => vec![],
+            Payload::Preendorse(_) | Payload::Endorse(_) if (msg.level, msg.round_id()) > current || waiting => {
+                self.early.push((signer_id, msg));
+                vec![]
+            },
+            Payload::Preendorse(block_hash) => {
+                let round_id = u64::from(msg.round_id);
+                let preendorsement = blockchain::Preendorsement::new(signer_id, msg.level, round_id, block_hash);
+                self.on_preendorsement(time, signer_id, preendorsement)
+            },
+            Payload::Endorse(Endorse { block_hash, pqc }) => {
+                let round_id = u64::from(msg.round_id);
+                let endorsement = blockchain::Endorsement::new(signer_id, msg.level, round_id, block_hash);
+                self.on_endorsement(time, signer_id, endorsement, pqc)
+            },
+            Payload::Preendorsements(Preendorsements { block, pqc }) => {
+                if msg.level == self.current_level() {
+                    self.on_preendorsements(block, pqc);
+                }
+                vec![]
+            },
+            Payload::Transaction(transaction) => {
+                let _ = self.mempool.add(transaction, time);
+                vec![]
+            },
+            Payload::Request(request) => self.on_request(signer_id, request),
+            Payload::Blocks(Blocks { blocks, eqc }) => self.on_blocks(time, blocks, eqc),
+        }
+    }
+}
+
+impl Decisions for TenderbakeNode {
+    type Value = blockchain::BlockHash;
+
+    // without the endorsement quorum of the head it may still be replaced
+    // by a block of a higher round, every block below it is decided
+    fn decided_levels(&self) -> usize {
+        match self.head_eqc {
+            Some(_) => self.branch.len(),
+            None => self.branch.len().saturating_sub(1),
+        }
+    }
+
+    fn decision(&self, level: usize) -> Option<Self::Value> {
+        self.branch[..self.decided_levels()].get(level).cloned()
+    }
 }
 
-fn is_proposer(node_id: usize, total_nodes: usize, level: u64, round_id: u64) -> bool {
-    node_id == ((level + round_id) as usize) % total_nodes
+impl SyncTimes for TenderbakeNode {
+    // from receiving a message of a level at least two ahead until deciding
+    // the levels it proves decided, by the local clock
+    fn sync_times(&self) -> &[Duration] {
+        &self.sync_times
+    }
+}
+
+fn is_proposer(node_id: usize, total_nodes: usize, level: u32, round_id: u64) -> bool {
+    node_id == ((u64::from(level) + round_id) as usize) % total_nodes
 }
 
 impl Node for TenderbakeNode {
-    type Message = blockchain::Signed<Msg>;
+    type Message = Msg;
+    type Input = blockchain::Transaction;
 
-    fn handle_event(&mut self, time: Duration, event: &NodeEvent<Self::Message>) -> Vec<Effect<Self::Message>> {
+    fn handle_event(
+        &mut self,
+        time: Duration,
+        event: &NodeEvent<Self::Message, Self::Input>,
+    ) -> Vec<Effect<Self::Message>> {
         match event {
-            &NodeEvent::MessageReceived(ref message) => {
-                if let Some((msg, signer_id)) = message.clone().check() {
-                    match msg.payload {
-                        Payload::Propose(candidate_chain) => {
-                            let is_proposer_valid = is_proposer(
-                                signer_id,
-                                self.config.total_nodes,
-                                msg.level,
-                                msg.round_id,
-                            );
-                            let previously_proposed_pqc_is_correct = match candidate_chain.head() {
-                                None => true,
-                                Some(head) => head.is_pqc_valid(&self.config),
-                            };
-                            let round = self.endorsable.as_ref();
-                            let ok = true
-                                && is_proposer_valid
-                                && previously_proposed_pqc_is_correct
-                                && candidate_chain.valid_chain(&self.config)
-                                && candidate_chain.better_chain(&self.chain, round);
-                            if ok {
-                                let candidate_timestamp = candidate_chain
-                                    .head()
-                                    .expect("impossible")
-                                    .timestamp();
-                                let _ = candidate_timestamp;
-                                unimplemented!()
-                            } else {
-                                vec![]
-                            }
-                        },
-                        Payload::Preendorse(blockchain::Preendorsement(p)) => {
-                            let _ = p;
-                            unimplemented!()
-                        },
-                        Payload::Endorse(blockchain::Endorsement(e), pqc) => {
-                            let _ = (e, pqc);
-                            unimplemented!()
-                        },
-                        Payload::Preendorsements(block, pqc) => {
-                            let _ = (block, pqc);
-                            unimplemented!()
-                        }
-                    }
+            NodeEvent::MessageReceived(message) => {
+                let mut effects = self.on_msg(time, message.signer(), message.clone());
+                // a decision or a proposal of a later level moves the node forward
+                effects.extend(self.tick(time));
+                effects
+            },
+            NodeEvent::Start { .. } | NodeEvent::WakeUp => self.tick(time),
+            NodeEvent::Input(transaction) => {
+                if self.mempool.add(transaction.clone(), time) {
+                    vec![self.broadcast(Payload::Transaction(transaction.clone()))]
                 } else {
                     vec![]
                 }
             },
-            NodeEvent::WakeUp => {
-                let _ = time;
-                vec![]
-            },
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        time::Duration,
+    };
+    use crate::{
+        Node, NodeEvent, Effect, Simulator, CaseNetwork, DelayModel, Agreement, Invariant, Metrics, Observer,
+        MessageSize, MessageKind, Explorer, Validity, Trace, replay,
+    };
+    use super::super::{blockchain, config::TenderbakeConfig, ledger::StateAgreement};
+    use super::{TenderbakeNode, Msg, Payload, Endorse, Preendorsements, Request, Blocks};
+
+    const ROUND0: Duration = Duration::from_secs(1);
+
+    // runs until `until`, checking agreement on blocks and on ledger states
+    fn run(nodes: usize, crashed: &[usize], until: Duration) -> Vec<Option<TenderbakeNode>> {
+        let delay = DelayModel::Uniform {
+            min: Duration::from_millis(5),
+            max: Duration::from_millis(50),
+        };
+        let configs = TenderbakeConfig::new(nodes, ROUND0, 0);
+        let mut simulator = Simulator::new(configs.map(TenderbakeNode::new), CaseNetwork::new(delay, 0));
+        for &node_id in crashed {
+            simulator.shut_down(node_id);
+        }
+        let mut invariant = (Agreement::new(), StateAgreement::new());
+        simulator.run_until(1_000_000, &mut (), |time, nodes| {
+            invariant.check(time, nodes).unwrap();
+            time >= until
+        });
+        simulator.nodes().map(|node| node.cloned()).collect()
+    }
+
+    #[test]
+    fn decides_a_level_per_round() {
+        for node in run(4, &[], ROUND0 * 20).iter().flatten() {
+            assert!(node.finalized_level() >= 18, "{}", node.finalized_level());
+        }
+    }
+
+    #[test]
+    fn decides_with_a_crashed_node() {
+        let nodes = run(4, &[3], ROUND0 * 40);
+        for node in nodes.iter().flatten() {
+            assert!(node.finalized_level() >= 10, "{}", node.finalized_level());
+        }
+    }
+
+    #[test]
+    fn stalls_without_a_quorum() {
+        for node in run(4, &[2, 3], ROUND0 * 20).iter().flatten() {
+            assert_eq!(node.finalized_level(), 0);
+        }
+    }
+
+    // every schedule with at most one message or wake up out of order, deep
+    // enough for two levels to be decided
+    #[test]
+    fn explored_schedules_agree_over_two_levels() {
+        let nodes = || TenderbakeConfig::new(4, ROUND0, 0).map(TenderbakeNode::new);
+        let explorer = || Explorer::new(60, 100_000).with_delay_bound(1);
+
+        let second_level = Validity::new(|level, _: &blockchain::BlockHash| level < 1);
+        let decided = explorer().explore(nodes(), &second_level).counterexample;
+        assert!(decided.is_some(), "two levels are decided within the depth");
+
+        let exploration = explorer().explore(nodes(), &(Agreement::new(), StateAgreement::new()));
+        assert!(exploration.counterexample.is_none(), "{:?}", exploration.counterexample.map(|c| c.violation));
+        assert!(exploration.states < 100_000);
+    }
+
+    // transfers from account 0, injected at node 0 and gossiped to the others
+    #[test]
+    fn transactions_are_included_and_finalized() {
+        let transactions = (0..6).map(|nonce| blockchain::Transaction::new(0, 1, 10, nonce)).collect::<Vec<_>>();
+        // two transactions per block
+        let max_block_size = transactions[0].size() * 2;
+        let configs = TenderbakeConfig::new(4, ROUND0, 0).map(|config| TenderbakeConfig { max_block_size, ..config });
+        let delay = DelayModel::Constant(Duration::from_millis(10));
+        let mut simulator = Simulator::new(configs.map(TenderbakeNode::new), CaseNetwork::new(delay, 0));
+        for transaction in &transactions {
+            simulator.inject(ROUND0 / 2, 0, transaction.clone());
+        }
+        simulator.run_until(1_000_000, &mut (), |time, _| time >= ROUND0 * 10);
+
+        for node in simulator.nodes().flatten() {
+            let included = node
+                .finalized_blocks()
+                .flat_map(|block| block.contents().transactions().to_vec())
+                .collect::<Vec<_>>();
+            assert_eq!(included, transactions);
+            for block in node.finalized_blocks() {
+                assert!(block.contents().transactions().len() <= 2);
+            }
+            assert!(node.mempool().is_empty());
+            // every node received each of them, node 0 at the injection
+            let latencies = node.transaction_latencies();
+            assert_eq!(latencies.len(), transactions.len());
+            assert!(latencies.iter().all(|&latency| latency < ROUND0 * 4), "{:?}", latencies);
+        }
+    }
+
+    // transfers injected at different nodes, one of them can never apply
+    #[test]
+    fn every_node_computes_the_same_balances() {
+        let delay = DelayModel::Uniform {
+            min: Duration::from_millis(5),
+            max: Duration::from_millis(50),
+        };
+        let configs = TenderbakeConfig::new(4, ROUND0, 0);
+        let mut simulator = Simulator::new(configs.map(TenderbakeNode::new), CaseNetwork::new(delay, 0));
+        let transfers = [
+            (ROUND0 / 2, 0, blockchain::Transaction::new(0, 1, 100, 0)),
+            (ROUND0, 3, blockchain::Transaction::new(0, 3, 30, 1)),
+            (ROUND0 * 2, 1, blockchain::Transaction::new(1, 2, 50, 0)),
+            (ROUND0 * 2, 2, blockchain::Transaction::new(5, 2, 2_000_000, 0)),
+        ];
+        for (time, node_id, transaction) in transfers.iter().cloned() {
+            simulator.inject(time, node_id, transaction);
+        }
+        let mut invariant = (Agreement::new(), StateAgreement::new());
+        simulator.run_until(1_000_000, &mut (), |time, nodes| {
+            invariant.check(time, nodes).unwrap();
+            time >= ROUND0 * 10
+        });
+
+        let nodes = simulator.nodes().flatten().collect::<Vec<_>>();
+        for node in &nodes {
+            let ledger = node.ledger();
+            let balances = (0..6).map(|account| ledger.balance(account).unwrap()).collect::<Vec<_>>();
+            assert_eq!(balances, [999_870, 1_000_050, 1_000_050, 1_000_030, 1_000_000, 1_000_000]);
+            assert_eq!(node.state_hashes().len(), node.finalized_level() as usize);
+        }
+        let shortest = nodes.iter().map(|node| node.state_hashes().len()).min().unwrap();
+        assert!(shortest >= 8);
+        for node in &nodes {
+            assert_eq!(node.state_hashes()[..shortest], nodes[0].state_hashes()[..shortest]);
+        }
+    }
+
+    // a block is accepted only if it claims the state its transfers lead to
+    #[test]
+    fn wrong_state_hash_is_rejected() {
+        let node = TenderbakeNode::new(TenderbakeConfig::new(4, ROUND0, 0).next().unwrap());
+        let transaction = blockchain::Transaction::new(0, 1, 10, 0);
+        let block = |state_hash| {
+            let contents = blockchain::BlockContents::new(vec![transaction.clone()], 1, None, state_hash);
+            blockchain::Block::new(contents, 0, Duration::default(), vec![], None)
+        };
+        let mut ledger = node.ledger().clone();
+        ledger.apply(&transaction).unwrap();
+        assert_eq!(node.execute(&block(ledger.hash())), Some(ledger));
+        assert!(node.execute(&block(node.ledger().hash())).is_none());
+    }
+
+    // the messages the node broadcasts in reaction to `events`
+    fn broadcasts(
+        node: &mut TenderbakeNode,
+        events: Vec<(Duration, NodeEvent<Msg, blockchain::Transaction>)>,
+    ) -> Vec<Msg> {
+        events
+            .into_iter()
+            .flat_map(|(time, event)| node.handle_event(time, &event))
+            .filter_map(|effect| match effect {
+                Effect::Broadcast(msg) => Some(msg),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // node 1 sees the preendorsement quorum of round 0, node 0 does not,
+    // when round 1 starts node 1 tells it
+    #[test]
+    fn endorsable_round_is_shared_when_the_round_ends() {
+        let mut nodes = TenderbakeConfig::new(4, ROUND0, 0).map(TenderbakeNode::new).collect::<Vec<_>>();
+        let start = |node_id| (Duration::default(), NodeEvent::Start { node_id, node_number: 4 });
+        let received = |msg: &Msg| (Duration::from_millis(10), NodeEvent::MessageReceived(msg.clone()));
+
+        let proposal = broadcasts(&mut nodes[1], vec![start(1)]).into_iter().find(Msg::is_proposal).unwrap();
+        let mut preendorsements = vec![];
+        for (node_id, node) in nodes.iter_mut().enumerate().skip(2) {
+            let events = vec![start(node_id), received(&proposal)];
+            preendorsements.extend(broadcasts(node, events).into_iter().filter(Msg::is_preendorsement));
+        }
+        let _ = broadcasts(&mut nodes[1], preendorsements.iter().map(received).collect());
+        assert_eq!(nodes[1].endorsable().map(|round| round.round_id()), Some(0));
+
+        let _ = broadcasts(&mut nodes[0], vec![start(0)]);
+        assert!(nodes[0].endorsable().is_none());
+        let shared = broadcasts(&mut nodes[1], vec![(ROUND0, NodeEvent::WakeUp)])
+            .into_iter()
+            .find(|msg| msg.kind() == "preendorsements")
+            .expect("node 1 shares the quorum when round 1 starts");
+        assert_eq!((shared.level(), shared.round_id()), (1, 0));
+        let events = vec![(ROUND0, NodeEvent::WakeUp), (ROUND0, NodeEvent::MessageReceived(shared))];
+        let _ = broadcasts(&mut nodes[0], events);
+        let endorsable = nodes[0].endorsable().expect("node 0 learns the quorum");
+        assert_eq!(endorsable.round_id(), 0);
+        assert_eq!(endorsable.block_contents().hash(), nodes[1].head().unwrap().hash());
+    }
+
+    // the proposal of round 1 arrives while the node is still in round 0
+    #[test]
+    fn proposal_of_a_later_round_is_buffered() {
+        let mut configs = TenderbakeConfig::new(4, ROUND0, 0);
+        let mut node = TenderbakeNode::new(configs.next().unwrap());
+        let mut proposer = TenderbakeNode::new(configs.nth(1).unwrap());
+        let start = |node_id| NodeEvent::Start { node_id, node_number: 4 };
+
+        let _ = node.handle_event(Duration::default(), &start(0));
+        let proposal = proposer
+            .handle_event(ROUND0, &start(2))
+            .into_iter()
+            .find_map(|effect| match effect {
+                Effect::Broadcast(msg) if msg.is_proposal() => Some(msg),
+                _ => None,
+            })
+            .expect("node 2 proposes in round 1 of level 1");
+        assert_eq!((proposal.level(), proposal.round_id()), (1, 1));
+
+        let preendorses = |effects: Vec<Effect<super::Msg>>| {
+            effects.iter().any(|effect| matches!(effect, Effect::Broadcast(msg) if msg.is_preendorsement()))
+        };
+        let early = node.handle_event(ROUND0 - Duration::from_millis(1), &NodeEvent::MessageReceived(proposal));
+        assert!(!preendorses(early));
+        assert!(preendorses(node.handle_event(ROUND0, &NodeEvent::WakeUp)));
+        assert_eq!(node.head().map(|head| head.round()), Some(1));
+    }
+
+    #[test]
+    fn late_node_catches_up() {
+        let delay = DelayModel::Constant(Duration::from_millis(10));
+        let configs = TenderbakeConfig::new(4, ROUND0, 0);
+        let mut simulator = Simulator::new(configs.map(TenderbakeNode::new), CaseNetwork::new(delay, 0))
+            .with_start_times(vec![Duration::default(), Duration::default(), Duration::default(), ROUND0 * 10]);
+        let mut metrics = Metrics::new();
+        simulator.run_until(1_000_000, &mut metrics, |time, _| time >= ROUND0 * 20);
+        metrics.record_sync_times(simulator.nodes());
+
+        let late = simulator.nodes().nth(3).flatten().unwrap();
+        assert!(late.finalized_level() >= 15, "{}", late.finalized_level());
+        assert_eq!(metrics.sync_summary().count, 1);
+        assert!(metrics.sync_summary().max < ROUND0.as_secs_f64());
+    }
+
+    // keeps the first vote of a level the node does not follow yet
+    struct Ahead(Option<Msg>);
+
+    impl Observer<TenderbakeNode> for Ahead {
+        fn on_send(&mut self, _: Duration, _: usize, _: usize, message: &Msg) {
+            if self.0.is_none() && message.level() >= 3 && message.is_endorsement() {
+                self.0 = Some(message.clone());
+            }
+        }
+    }
+
+    // a lagging node asks again once the current round passed since the
+    // last request, not since it found out it is behind
+    #[test]
+    fn sync_requests_are_throttled() {
+        let delay = DelayModel::Constant(Duration::from_millis(10));
+        let configs = TenderbakeConfig::new(4, ROUND0, 0);
+        let mut simulator = Simulator::new(configs.map(TenderbakeNode::new), CaseNetwork::new(delay, 0));
+        let mut ahead = Ahead(None);
+        simulator.run_until(1_000_000, &mut ahead, |time, _| time >= ROUND0 * 5);
+        let message = ahead.0.expect("the nodes reach the third level");
+
+        let mut node = TenderbakeNode::new(TenderbakeConfig::new(4, ROUND0, 0).next().unwrap());
+        let _ = node.handle_event(Duration::default(), &NodeEvent::Start { node_id: 0, node_number: 4 });
+        let mut requests = |millis| {
+            let event = NodeEvent::MessageReceived(message.clone());
+            node.handle_event(Duration::from_millis(millis), &event)
+                .iter()
+                .filter(|effect| matches!(effect, Effect::Send(_, Msg { payload: Payload::Request(_), .. })))
+                .count()
+        };
+        // the node enters round 1 at 1 s, and round 2 at 3 s
+        assert_eq!(requests(0), 1);
+        assert_eq!(requests(500), 0);
+        assert_eq!(requests(1_000), 1);
+        assert_eq!(requests(2_000), 0);
+        assert_eq!(requests(3_000), 1);
+        assert_eq!(requests(3_500), 0);
+    }
+
+    // a block of the second round whose contents were proposed in the first
+    fn reproposed() -> blockchain::Block {
+        let transaction = blockchain::Transaction::new(0, 1, 10, 0);
+        let contents = blockchain::BlockContents::new(vec![transaction], 1, None, blockchain::StateHash::digest(&[]));
+        let pqc = (0..3).map(|id| blockchain::Preendorsement::new(id, 1, 0, contents.hash())).collect();
+        let pqc = blockchain::QuorumCertificate::new(pqc);
+        blockchain::Block::new(contents, 1, ROUND0, vec![], Some(pqc))
+    }
+
+    #[test]
+    fn every_payload_survives_encoding() {
+        let node = TenderbakeNode::new(TenderbakeConfig::new(4, ROUND0, 0).nth(2).unwrap());
+        let block = reproposed();
+        let hash = block.hash();
+        let pqc = block.previously_proposed().unwrap().preendorsements().to_vec();
+        let endorsement = blockchain::Endorsement::new(2, 1, 1, hash.clone());
+        let payloads = vec![
+            Payload::Propose(block.clone()),
+            Payload::Preendorse(hash.clone()),
+            Payload::Endorse(Endorse {
+                block_hash: hash.clone(),
+                pqc: pqc.clone(),
+            }),
+            Payload::Preendorsements(Preendorsements {
+                block: block.clone(),
+                pqc,
+            }),
+            Payload::Transaction(blockchain::Transaction::new(3, 0, 5, 1)),
+            Payload::Request(Request {
+                hash: Some(hash),
+                level: 1,
+                decided: 0,
+            }),
+            Payload::Request(Request {
+                hash: None,
+                level: 7,
+                decided: 3,
+            }),
+            Payload::Blocks(Blocks {
+                blocks: vec![block],
+                eqc: vec![endorsement],
+            }),
+        ];
+        for payload in payloads {
+            let msg = node.message(payload);
+            let bytes = msg.encode();
+            let decoded = Msg::decode(&bytes).unwrap();
+            assert_eq!(decoded.encode(), bytes, "{}", msg.kind());
+            assert_eq!(decoded.kind(), msg.kind());
+            assert_eq!((decoded.level(), decoded.round_id(), decoded.signer()), (1, 0, 2));
+            assert_eq!(msg.size(), bytes.len());
+        }
+    }
+
+    fn hash(value: &impl Hash) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // nodes, the messages they exchanged and the trace of the run go to
+    // json and back unchanged
+    #[test]
+    fn json_round_trip() {
+        let nodes = || TenderbakeConfig::new(4, ROUND0, 0).map(TenderbakeNode::new);
+        let delay = DelayModel::Constant(Duration::from_millis(10));
+        let mut simulator = Simulator::new(nodes(), CaseNetwork::new(delay, 0));
+        simulator.inject(ROUND0 / 2, 1, blockchain::Transaction::new(0, 1, 10, 0));
+        let mut trace = Trace::new();
+        simulator.run_until(1_000_000, &mut trace, |time, _| time >= ROUND0 * 5);
+
+        for node in simulator.nodes().flatten() {
+            assert!(node.finalized_level() >= 3);
+            let back = serde_json::from_str::<TenderbakeNode>(&serde_json::to_string(node).unwrap()).unwrap();
+            assert_eq!(hash(&back), hash(node));
+        }
+
+        let json = serde_json::to_string(&trace).unwrap();
+        let back = serde_json::from_str::<Trace<Msg, blockchain::Transaction>>(&json).unwrap();
+        assert_eq!(back.len(), trace.len());
+        for (step, back) in trace.steps().iter().zip(back.steps()) {
+            assert_eq!((step.time, step.node_id), (back.time, back.node_id));
+            assert_eq!(hash(&step.event), hash(&back.event));
+        }
+        assert!(trace.steps().iter().any(|step| matches!(step.event, NodeEvent::Input(_))));
+        // the decoded trace brings the nodes to the same states
+        let recorded = simulator.nodes().map(|node| hash(&node)).collect::<Vec<_>>();
+        assert_eq!(recorded, replay(nodes(), &back).iter().map(|node| hash(&node.as_ref())).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    sync::{Mutex, atomic::{AtomicUsize, Ordering}},
+    thread,
+};
+use super::{network::mix, metrics::Summary};
+
+pub struct Run<'a, P, R> {
+    pub parameters: &'a P,
+    pub seed: u64,
+    pub result: R,
+}
+
+// runs every parameter set with the same list of seeds, so the results of
+// different parameters are compared on the same random scenarios
+pub struct Batch<P> {
+    parameters: Vec<P>,
+    seed: u64,
+    runs: usize,
+    threads: usize,
+}
+
+impl<P> Batch<P> {
+    pub fn new<I>(parameters: I, seed: u64) -> Self
+    where
+        I: IntoIterator<Item = P>,
+    {
+        Batch {
+            parameters: parameters.into_iter().collect(),
+            seed,
+            runs: 1,
+            threads: thread::available_parallelism().map(usize::from).unwrap_or(1),
+        }
+    }
+
+    // number of runs for each parameter set
+    pub fn runs(self, runs: usize) -> Self {
+        Batch { runs, ..self }
+    }
+
+    pub fn threads(self, threads: usize) -> Self {
+        assert!(threads > 0);
+        Batch { threads, ..self }
+    }
+
+    pub fn seeds(&self) -> impl Iterator<Item = u64> {
+        let seed = self.seed;
+        (0..self.runs as u64).map(move |run| mix(&[seed, run]))
+    }
+
+    // each run is executed by `f` on its own, the order of the result does not
+    // depend on the number of threads, grouped by parameters and then by seed
+    pub fn run<R, F>(&self, f: F) -> Vec<Run<'_, P, R>>
+    where
+        P: Sync,
+        R: Send,
+        F: Fn(&P, u64) -> R + Sync,
+    {
+        let seeds = self.seeds().collect::<Vec<_>>();
+        let total = self.parameters.len() * seeds.len();
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new((0..total).map(|_| None).collect::<Vec<_>>());
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(total) {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= total {
+                        break;
+                    }
+                    let parameters = &self.parameters[index / seeds.len()];
+                    let result = f(parameters, seeds[index % seeds.len()]);
+                    results.lock().unwrap()[index] = Some(result);
+                });
+            }
+        });
+        results
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| Run {
+                parameters: &self.parameters[index / seeds.len()],
+                seed: seeds[index % seeds.len()],
+                result: result.expect("every run is finished when the scope ends"),
+            })
+            .collect()
+    }
+}
+
+// summary of `value` over the runs of each parameter set, in the order of the parameters
+pub fn aggregate<'a, P, R, F>(runs: &[Run<'a, P, R>], mut value: F) -> Vec<(&'a P, Summary)>
+where
+    F: FnMut(&R) -> Option<f64>,
+{
+    let mut groups: Vec<(&'a P, Vec<f64>)> = vec![];
+    for run in runs {
+        let same = groups
+            .last()
+            .map(|&(parameters, _)| std::ptr::eq(parameters, run.parameters))
+            .unwrap_or(false);
+        if !same {
+            groups.push((run.parameters, vec![]));
+        }
+        if let Some(v) = value(&run.result) {
+            groups.last_mut().unwrap().1.push(v);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(parameters, samples)| (parameters, Summary::new(samples)))
+        .collect()
+}
+
+// cartesian product of two parameter axes
+pub fn grid<A, B>(a: A, b: B) -> Vec<(A::Item, B::Item)>
+where
+    A: IntoIterator,
+    A::Item: Clone,
+    B: IntoIterator,
+    B::Item: Clone,
+{
+    let b = b.into_iter().collect::<Vec<_>>();
+    a.into_iter()
+        .flat_map(|a| b.iter().map(move |b| (a.clone(), b.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{Simulator, CaseNetwork, DelayModel, BenOrNode, BenOrConfig};
+    use super::{Batch, grid};
+
+    // the decision of every node and the time the simulation ended
+    fn simulate(&(total_nodes, faulty): &(usize, usize), seed: u64) -> (Vec<Option<(bool, u32)>>, Duration) {
+        let delay = DelayModel::Exponential {
+            mean: Duration::from_millis(20),
+        };
+        let nodes = BenOrConfig::new(total_nodes, faulty, seed).map(BenOrNode::new);
+        let mut simulator = Simulator::new(nodes, CaseNetwork::new(delay, seed));
+        let mut end = Duration::default();
+        simulator.run_until(100_000, &mut (), |time, _| {
+            end = time;
+            false
+        });
+        let decisions = simulator.nodes().map(|node| node.and_then(BenOrNode::decided)).collect();
+        (decisions, end)
+    }
+
+    #[test]
+    fn results_do_not_depend_on_threads() {
+        let results = |threads| {
+            Batch::new(grid(vec![4, 7], vec![0, 1]), 3)
+                .runs(5)
+                .threads(threads)
+                .run(simulate)
+                .into_iter()
+                .map(|run| (*run.parameters, run.seed, run.result))
+                .collect::<Vec<_>>()
+        };
+        let sequential = results(1);
+        assert_eq!(sequential.len(), 4 * 5);
+        assert_eq!(sequential, results(4));
+        assert_eq!(sequential, results(7));
+    }
+}
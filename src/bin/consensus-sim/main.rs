@@ -0,0 +1,141 @@
+mod scenario;
+
+use std::{env, fmt, fs, io, path::Path, process, time::Duration};
+use consensus_simulator::{
+    Node, Simulator, Network, Decisions, Metrics, MessageKind, MessageSize,
+    random_start_times, random_clocks,
+    LeaderElectionNode, BenOrNode, BenOrConfig, StreamletNode, StreamletConfig,
+};
+#[cfg(feature = "tenderbake")]
+use consensus_simulator::{TenderbakeNode, TenderbakeConfig};
+use self::scenario::{Scenario, Algorithm, Stream};
+
+const USAGE: &str = "usage: consensus-sim run <scenario.toml> [--seed <seed>]";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["run", path] => run(Path::new(path), None),
+        ["run", path, "--seed", seed] => match seed.parse() {
+            Ok(seed) => run(Path::new(path), Some(seed)),
+            Err(e) => Err(format!("invalid seed {}: {}", seed, e)),
+        },
+        ["help"] | ["--help"] | ["-h"] => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        },
+    };
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run(path: &Path, seed: Option<u64>) -> Result<(), String> {
+    let mut scenario = Scenario::load(path)?;
+    if let Some(seed) = seed {
+        scenario.seed = seed;
+    }
+    let base = path.parent().unwrap_or_else(|| Path::new("."));
+    let (n, seed) = (scenario.nodes, scenario.sub_seed(Stream::Algorithm));
+    match scenario.algorithm {
+        Algorithm::LeaderElection => {
+            let nodes = (0..n).map(|_| LeaderElectionNode::good());
+            simulate(&scenario, base, nodes, scenario.network(base)?, |_, _| ())
+        },
+        Algorithm::BenOr { faulty } => {
+            if n <= 2 * faulty {
+                return Err("Ben-Or requires `nodes > 2 * faulty`".to_string());
+            }
+            let nodes = BenOrConfig::new(n, faulty, seed).map(BenOrNode::new);
+            simulate(&scenario, base, nodes, scenario.network(base)?, |_, _| ())
+        },
+        Algorithm::Streamlet { epoch_duration_ms } => {
+            let epoch_duration = Duration::from_millis(epoch_duration_ms);
+            let nodes = StreamletConfig::new(n, epoch_duration).map(StreamletNode::new);
+            simulate(&scenario, base, nodes, scenario.network(base)?, |_, _| ())
+        },
+        #[cfg(feature = "tenderbake")]
+        Algorithm::Tenderbake { round0_duration_ms } => {
+            let round0_duration = Duration::from_millis(round0_duration_ms);
+            let nodes = TenderbakeConfig::new(n, round0_duration, seed).map(TenderbakeNode::new);
+            let sync = |metrics: &mut Metrics<_>, nodes: &[Option<&TenderbakeNode>]| {
+                metrics.record_sync_times(nodes.iter().cloned())
+            };
+            simulate(&scenario, base, nodes, scenario.network(base)?, sync)
+        },
+        #[cfg(not(feature = "tenderbake"))]
+        Algorithm::Tenderbake { .. } => {
+            Err("built without the `tenderbake` feature".to_string())
+        },
+    }
+}
+
+// `finish` adds to the metrics what only some nodes report
+fn simulate<N>(
+    scenario: &Scenario,
+    base: &Path,
+    nodes: impl Iterator<Item = N>,
+    network: Box<dyn Network<N::Message>>,
+    finish: impl FnOnce(&mut Metrics<N::Value>, &[Option<&N>]),
+) -> Result<(), String>
+where
+    N: Node + Decisions,
+    N::Message: MessageKind<Proposal = N::Value> + MessageSize + fmt::Debug,
+    N::Input: fmt::Debug,
+{
+    let start = &scenario.start;
+    let mut simulator = Simulator::new(nodes, network)
+        .with_start_times(random_start_times(scenario.sub_seed(Stream::StartTimes), Duration::from_millis(start.max_skew_ms)))
+        .with_clocks(random_clocks(
+            scenario.sub_seed(Stream::Clocks),
+            Duration::from_millis(start.max_clock_offset_ms),
+            start.max_clock_drift,
+        ))
+        .with_faults(scenario.faults.clone());
+
+    let stop = &scenario.stop;
+    let time_limit = stop.time_ms.map(Duration::from_millis);
+    let mut metrics = Metrics::new();
+    simulator.run_until(stop.iterations, &mut metrics, |time, nodes| {
+        let timeout = time_limit.map(|limit| time >= limit).unwrap_or(false);
+        let decided = stop.levels
+            .map(|levels| nodes.iter().flatten().all(|node| node.decided_levels() >= levels))
+            .unwrap_or(false);
+        timeout || decided
+    });
+    finish(&mut metrics, &simulator.nodes().collect::<Vec<_>>());
+
+    report(&metrics, &mut io::stdout()).map_err(|e| e.to_string())?;
+    if let Some(path) = &scenario.output.metrics_json {
+        let path = base.join(path);
+        let file = fs::File::create(&path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?;
+        metrics.write_json(file).map_err(|e| e.to_string())?;
+    }
+    if let Some(path) = &scenario.output.metrics_csv {
+        let path = base.join(path);
+        let file = fs::File::create(&path).map_err(|e| format!("cannot create {}: {}", path.display(), e))?;
+        metrics.write_csv(file).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn report<V>(metrics: &Metrics<V>, w: &mut impl io::Write) -> io::Result<()> {
+    writeln!(w, "simulated time: {:?}", metrics.end_time())?;
+    writeln!(w, "finalized levels: {}", metrics.levels().len())?;
+    writeln!(w, "throughput: {:.3} levels/s", metrics.throughput())?;
+    writeln!(w, "latency, s: {}", metrics.latency_summary())?;
+    writeln!(w, "rounds: {}", metrics.rounds_summary())?;
+    let sync = metrics.sync_summary();
+    if sync.count > 0 {
+        writeln!(w, "sync, s: {}", sync)?;
+    }
+    for (kind, traffic) in metrics.traffic() {
+        writeln!(w, "{}: {} messages, {} bytes", kind, traffic.messages, traffic.bytes)?;
+    }
+    Ok(())
+}
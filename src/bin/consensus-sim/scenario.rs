@@ -0,0 +1,220 @@
+use std::{fs, path::{Path, PathBuf}, time::Duration};
+use serde::Deserialize;
+use consensus_simulator::{Network, CaseNetwork, DelayModel, MatrixNetwork, Fault, FaultSchedule, mix};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub seed: u64,
+    pub nodes: usize,
+    pub algorithm: Algorithm,
+    pub network: NetworkModel,
+    #[serde(default)]
+    pub start: Start,
+    #[serde(default)]
+    pub faults: FaultSchedule,
+    #[serde(default)]
+    pub stop: Stop,
+    #[serde(default)]
+    pub output: Output,
+}
+
+// each random part of a run draws from its own seed, derived from `Scenario::seed`
+#[derive(Clone, Copy)]
+pub enum Stream {
+    Algorithm,
+    Network,
+    StartTimes,
+    Clocks,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum Algorithm {
+    LeaderElection,
+    BenOr {
+        faulty: usize,
+    },
+    Streamlet {
+        epoch_duration_ms: u64,
+    },
+    Tenderbake {
+        // parsed either way, so a build without the feature says so
+        #[cfg_attr(not(feature = "tenderbake"), allow(dead_code))]
+        round0_duration_ms: u64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "model", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum NetworkModel {
+    Constant {
+        delay_ms: u64,
+    },
+    Uniform {
+        min_ms: u64,
+        max_ms: u64,
+    },
+    Exponential {
+        mean_ms: u64,
+    },
+    // `links` is a csv file, relative to the scenario file
+    Matrix {
+        regions: Vec<String>,
+        links: PathBuf,
+    },
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Start {
+    #[serde(default)]
+    pub max_skew_ms: u64,
+    #[serde(default)]
+    pub max_clock_offset_ms: u64,
+    #[serde(default)]
+    pub max_clock_drift: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stop {
+    #[serde(default = "Stop::default_iterations")]
+    pub iterations: usize,
+    pub time_ms: Option<u64>,
+    // every correct node has decided this many levels
+    pub levels: Option<usize>,
+}
+
+impl Stop {
+    fn default_iterations() -> usize {
+        1_000_000
+    }
+}
+
+impl Default for Stop {
+    fn default() -> Self {
+        Stop {
+            iterations: Stop::default_iterations(),
+            time_ms: None,
+            levels: None,
+        }
+    }
+}
+
+// paths are relative to the scenario file
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Output {
+    pub metrics_json: Option<PathBuf>,
+    pub metrics_csv: Option<PathBuf>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let scenario: Scenario = toml::from_str(&text)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn sub_seed(&self, stream: Stream) -> u64 {
+        mix(&[self.seed, stream as u64])
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.nodes == 0 {
+            return Err("`nodes` must be positive".to_string());
+        }
+        for fault in self.faults.faults() {
+            let nodes = match fault {
+                Fault::Crash { node_id, .. } | Fault::Byzantine { node_id, .. } => vec![*node_id],
+                Fault::Partition { left, right, .. } => left.iter().chain(right).cloned().collect(),
+            };
+            if let Some(node_id) = nodes.into_iter().find(|&id| id >= self.nodes) {
+                return Err(format!("fault {:?} refers to node {} that does not exist", fault, node_id));
+            }
+        }
+        if let NetworkModel::Uniform { min_ms, max_ms } = self.network {
+            if min_ms > max_ms {
+                return Err("uniform network requires `min_ms <= max_ms`".to_string());
+            }
+        }
+        if let NetworkModel::Matrix { ref regions, .. } = self.network {
+            if regions.len() != self.nodes {
+                return Err(format!("matrix network has {} regions for {} nodes", regions.len(), self.nodes));
+            }
+        }
+        // a clock must not run backward, so the drift is less than one
+        let drift = self.start.max_clock_drift;
+        if !(0.0..1.0).contains(&drift) {
+            return Err("`max_clock_drift` must be in `[0, 1)`".to_string());
+        }
+        Ok(())
+    }
+
+    // relative paths in the scenario are resolved against `base`
+    pub fn network<M>(&self, base: &Path) -> Result<Box<dyn Network<M>>, String> {
+        let ms = Duration::from_millis;
+        let delay = match self.network {
+            NetworkModel::Constant { delay_ms } => DelayModel::Constant(ms(delay_ms)),
+            NetworkModel::Uniform { min_ms, max_ms } => DelayModel::Uniform {
+                min: ms(min_ms),
+                max: ms(max_ms),
+            },
+            NetworkModel::Exponential { mean_ms } => DelayModel::Exponential { mean: ms(mean_ms) },
+            NetworkModel::Matrix { ref regions, ref links } => {
+                let path = base.join(links);
+                let csv = fs::read_to_string(&path)
+                    .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+                let regions = regions.iter().map(String::as_str).collect::<Vec<_>>();
+                let network = MatrixNetwork::from_csv(&regions, &csv, self.sub_seed(Stream::Network))
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                return Ok(Box::new(network));
+            },
+        };
+        Ok(Box::new(CaseNetwork::new(delay, self.sub_seed(Stream::Network))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::{Scenario, Algorithm, Stream};
+
+    #[test]
+    fn example_scenario_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios/streamlet.toml");
+        let scenario = Scenario::load(&path).unwrap();
+        assert_eq!(scenario.nodes, 7);
+        assert!(matches!(scenario.algorithm, Algorithm::Streamlet { epoch_duration_ms: 500 }));
+        assert_eq!(scenario.faults.faults().len(), 3);
+        assert_eq!(scenario.stop.levels, Some(50));
+        scenario.network::<usize>(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn streams_have_distinct_seeds() {
+        let scenario: Scenario = toml::from_str(
+            "seed = 1\nnodes = 4\n[algorithm]\nkind = \"leader-election\"\n[network]\nmodel = \"constant\"\ndelay_ms = 10\n",
+        )
+        .unwrap();
+        let streams = [Stream::Algorithm, Stream::Network, Stream::StartTimes, Stream::Clocks];
+        let mut seeds = streams.iter().map(|&s| scenario.sub_seed(s)).collect::<Vec<_>>();
+        seeds.sort();
+        seeds.dedup();
+        assert_eq!(seeds.len(), streams.len());
+    }
+
+    #[test]
+    fn clock_drift_is_checked() {
+        let base = "seed = 1\nnodes = 4\n[algorithm]\nkind = \"leader-election\"\n[network]\nmodel = \"constant\"\ndelay_ms = 10\n";
+        for (drift, valid) in [("0.0", true), ("0.5", true), ("-0.1", false), ("1.0", false), ("nan", false)] {
+            let text = format!("{}[start]\nmax_clock_drift = {}\n", base, drift);
+            let scenario: Scenario = toml::from_str(&text).unwrap();
+            assert_eq!(scenario.validate().is_ok(), valid, "drift {}", drift);
+        }
+    }
+}
@@ -1,19 +1,20 @@
 fn main() {
     use std::time::Duration;
-    use consensus_simulator::{Simulator, Network, TenderbakeNode, TenderbakeConfig};
+    use consensus_simulator::{Simulator, Network, TenderbakeNode, TenderbakeConfig, random_start_times};
 
     struct DefaultNetwork;
 
-    impl Network for DefaultNetwork {
+    impl<M> Network<M> for DefaultNetwork {
         fn delay(
-            &self,
+            &mut self,
             this: Duration,
             iteration: usize,
             effect_index: usize,
             sender_node_id: usize,
             receiver_node_id: usize,
+            message: &M,
         ) -> Duration {
-            let _ = (self, this, iteration, effect_index, sender_node_id, receiver_node_id);
+            let _ = (self, this, iteration, effect_index, sender_node_id, receiver_node_id, message);
             let r = rand::Rng::gen::<u8>(&mut rand::thread_rng()) / 6;
             Duration::from_millis(100 + (r as u64))
         }
@@ -21,6 +22,7 @@ fn main() {
 
     let seed = 0x123456;
     let configs = TenderbakeConfig::new(16, Duration::from_secs(3), seed);
-    let simulator = Simulator::new(configs.map(TenderbakeNode::new), DefaultNetwork);
+    let mut simulator = Simulator::new(configs.map(TenderbakeNode::new), DefaultNetwork)
+        .with_start_times(random_start_times(seed, Duration::from_millis(500)));
     simulator.run(1000);
 }
@@ -0,0 +1,161 @@
+use std::time::Duration;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    // local reading at global time zero in nanoseconds, negative if behind
+    offset: i128,
+    // `0.001` means the clock runs 0.1% fast
+    drift: f64,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::perfect()
+    }
+}
+
+impl Clock {
+    pub fn perfect() -> Self {
+        Clock {
+            offset: 0,
+            drift: 0.0,
+        }
+    }
+
+    pub fn ahead(offset: Duration, drift: f64) -> Self {
+        assert!(drift > -1.0, "clock must move forward");
+        Clock {
+            offset: offset.as_nanos() as i128,
+            drift,
+        }
+    }
+
+    pub fn behind(offset: Duration, drift: f64) -> Self {
+        assert!(drift > -1.0, "clock must move forward");
+        Clock {
+            offset: -(offset.as_nanos() as i128),
+            drift,
+        }
+    }
+
+    pub fn local(&self, global: Duration) -> Duration {
+        if *self == Clock::perfect() {
+            return global;
+        }
+        let nanos = (global.as_nanos() as f64 * (1.0 + self.drift)).floor() as i128 + self.offset;
+        Duration::from_nanos(nanos.max(0) as u64)
+    }
+
+    pub fn global(&self, local: Duration) -> Duration {
+        if *self == Clock::perfect() {
+            return local;
+        }
+        let nanos = ((local.as_nanos() as i128 - self.offset) as f64 / (1.0 + self.drift)).ceil();
+        let mut global = Duration::from_nanos(nanos.max(0.0) as u64);
+        // rounding must never wake the node up before its local deadline
+        while self.local(global) < local {
+            global += Duration::from_nanos(1);
+        }
+        global
+    }
+}
+
+pub fn random_clocks(
+    seed: u64,
+    max_offset: Duration,
+    max_drift: f64,
+) -> impl Iterator<Item = Clock> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let max_offset = max_offset.as_nanos() as u64;
+    std::iter::repeat_with(move || {
+        let offset = Duration::from_nanos(rng.gen_range(0..=max_offset));
+        let drift = rng.gen_range(-max_drift..=max_drift);
+        if rng.gen() {
+            Clock::ahead(offset, drift)
+        } else {
+            Clock::behind(offset, drift)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::Clock;
+
+    const NANOS: &[u64] = &[1, 999, 1_000_000, 123_456_789, 1_000_000_007, 3_600_000_000_017];
+
+    fn clocks() -> Vec<Clock> {
+        let ms = Duration::from_millis;
+        vec![
+            Clock::ahead(ms(250), 0.0),
+            Clock::ahead(ms(7), 0.001),
+            Clock::ahead(ms(0), -0.003),
+            Clock::behind(ms(250), 0.0),
+            Clock::behind(ms(13), 0.002),
+            Clock::behind(ms(1), -0.0007),
+        ]
+    }
+
+    #[test]
+    fn perfect_clock_reads_global_time() {
+        for &nanos in NANOS {
+            let time = Duration::from_nanos(nanos);
+            assert_eq!(Clock::perfect().local(time), time);
+            assert_eq!(Clock::perfect().global(time), time);
+        }
+    }
+
+    // a wake up at `global(local)` is never before the local deadline and
+    // not a nanosecond later than needed
+    #[test]
+    fn global_is_the_first_moment_the_clock_reads_local() {
+        for clock in clocks() {
+            for &nanos in NANOS {
+                let local = Duration::from_nanos(nanos);
+                let global = clock.global(local);
+                assert!(clock.local(global) >= local, "{:?} {:?}", clock, local);
+                if let Some(earlier) = global.checked_sub(Duration::from_nanos(1)) {
+                    assert!(clock.local(earlier) < local, "{:?} {:?}", clock, local);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reading_round_trips() {
+        for clock in clocks() {
+            for &nanos in NANOS {
+                let global = Duration::from_nanos(nanos);
+                let local = clock.local(global);
+                if local == Duration::default() {
+                    continue;
+                }
+                assert!(clock.global(local) <= global, "{:?} {:?}", clock, global);
+                assert_eq!(clock.local(clock.global(local)), local, "{:?} {:?}", clock, global);
+            }
+        }
+    }
+
+    #[test]
+    fn offset_and_drift() {
+        let ms = Duration::from_millis;
+        assert_eq!(Clock::ahead(ms(250), 0.0).local(ms(100)), ms(350));
+        assert_eq!(Clock::ahead(ms(0), 0.5).local(ms(100)), ms(150));
+        assert_eq!(Clock::ahead(ms(0), 0.5).global(ms(150)), ms(100));
+        assert_eq!(Clock::behind(ms(0), -0.5).local(ms(100)), ms(50));
+        // the clock is past the deadline from the start
+        assert_eq!(Clock::ahead(ms(250), 0.0).global(ms(100)), Duration::default());
+    }
+
+    #[test]
+    fn clock_behind_reads_zero_until_it_catches_up() {
+        let ms = Duration::from_millis;
+        let clock = Clock::behind(ms(250), 0.0);
+        assert_eq!(clock.local(ms(100)), Duration::default());
+        assert_eq!(clock.local(ms(250)), Duration::default());
+        assert_eq!(clock.local(ms(300)), ms(50));
+        assert_eq!(clock.global(ms(50)), ms(300));
+    }
+}
@@ -1,9 +1,11 @@
 use std::{cmp::Ordering, time::Duration};
+use super::overlay::Relay;
 
 #[derive(Debug)]
 pub struct EventItem<E> {
     metadata: EventMeta,
     node_id: usize,
+    relay: Option<Relay>,
     inner: E,
 }
 
@@ -22,10 +24,19 @@ impl<E> EventItem<E> {
                 effect_index,
             },
             node_id,
+            relay: None,
             inner: event,
         }
     }
 
+    pub fn with_relay(self, relay: Option<Relay>) -> Self {
+        EventItem { relay, ..self }
+    }
+
+    pub fn relay(&self) -> Option<Relay> {
+        self.relay
+    }
+
     pub fn node_id(&self) -> usize {
         self.node_id
     }
@@ -49,7 +60,7 @@ impl<E> Eq for EventItem<E> {}
 
 impl<E> PartialOrd for EventItem<E> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.metadata.partial_cmp(&other.metadata).map(Ordering::reverse)
+        Some(self.cmp(other))
     }
 }
 
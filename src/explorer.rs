@@ -0,0 +1,327 @@
+use std::{
+    collections::{HashSet, VecDeque, hash_map::DefaultHasher},
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+use super::{
+    node::{Node, NodeEvent, Effect},
+    invariant::{Invariant, Violation},
+};
+
+// exhaustive breadth first exploration of delivery orders, the network is
+// asynchronous: any pending message or wake up may be delivered next, time
+// only advances when a wake up fires
+pub struct Explorer {
+    max_depth: usize,
+    max_states: usize,
+    // how many times a schedule may deviate from delivering the oldest
+    // pending message, or the earliest wake up if there is none
+    delay_bound: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Step<M, I = ()> {
+    pub time: Duration,
+    pub node_id: usize,
+    pub event: NodeEvent<M, I>,
+}
+
+#[derive(Debug)]
+pub struct Counterexample<M, I = ()> {
+    pub trace: Vec<Step<M, I>>,
+    pub violation: Violation,
+}
+
+#[derive(Debug)]
+pub struct Exploration<M, I = ()> {
+    pub states: usize,
+    // every state reachable within the delay bound was visited, neither
+    // limit cut anything off
+    pub exhausted: bool,
+    pub counterexample: Option<Counterexample<M, I>>,
+}
+
+// the invariant remembers what it has seen, so every state has its own copy
+// of it, one that has only seen the states on the path to this one
+#[derive(Clone)]
+struct State<N, M, I, C> {
+    time: Duration,
+    nodes: Vec<Option<N>>,
+    wake_ups: Vec<Option<Duration>>,
+    pending: Vec<(usize, NodeEvent<M, I>)>,
+    trace: Vec<Step<M, I>>,
+    delays: usize,
+    invariant: C,
+}
+
+impl<N, M, I, C> State<N, M, I, C>
+where
+    N: Hash,
+    M: Hash,
+    I: Hash,
+{
+    fn fingerprint(&self) -> u64 {
+        let hash = |value: &dyn Fn(&mut DefaultHasher)| {
+            let mut hasher = DefaultHasher::new();
+            value(&mut hasher);
+            hasher.finish()
+        };
+        // pending messages are a multiset, their order does not matter
+        let mut pending = self.pending
+            .iter()
+            .map(|p| hash(&|h| p.hash(h)))
+            .collect::<Vec<_>>();
+        pending.sort_unstable();
+        hash(&|h| {
+            self.time.hash(h);
+            self.nodes.hash(h);
+            self.wake_ups.hash(h);
+            pending.hash(h);
+            self.delays.hash(h);
+        })
+    }
+
+    // the oldest pending message, or the earliest wake up
+    fn in_order(&self) -> Option<usize> {
+        if !self.pending.is_empty() {
+            return Some(0);
+        }
+        self.wake_ups
+            .iter()
+            .enumerate()
+            .filter_map(|(node_id, time)| time.map(|time| (time, node_id)))
+            .min()
+            .map(|(_, node_id)| self.pending.len() + node_id)
+    }
+}
+
+impl Explorer {
+    pub fn new(max_depth: usize, max_states: usize) -> Self {
+        Explorer {
+            max_depth,
+            max_states,
+            delay_bound: None,
+        }
+    }
+
+    // explores only the schedules that deviate from the in order one at most
+    // `bound` times, it reaches much deeper for the same number of states
+    pub fn with_delay_bound(mut self, bound: usize) -> Self {
+        self.delay_bound = Some(bound);
+        self
+    }
+
+    pub fn explore<N, C>(
+        &self,
+        nodes: impl Iterator<Item = N>,
+        invariant: &C,
+    ) -> Exploration<N::Message, N::Input>
+    where
+        N: Node + Clone + Hash,
+        N::Message: Hash,
+        N::Input: Clone + Hash,
+        C: Invariant<N> + Clone,
+    {
+        let nodes = nodes.map(Some).collect::<Vec<_>>();
+        let node_number = nodes.len();
+        let initial = State {
+            time: Duration::from_secs(0),
+            wake_ups: vec![None; node_number],
+            pending: (0..node_number)
+                .map(|node_id| (node_id, NodeEvent::Start { node_id, node_number }))
+                .collect(),
+            nodes,
+            trace: vec![],
+            delays: 0,
+            invariant: invariant.clone(),
+        };
+
+        let mut visited = HashSet::new();
+        visited.insert(initial.fingerprint());
+        let mut queue = VecDeque::new();
+        queue.push_back(initial);
+        let mut states = 1;
+        // a state at the maximal depth with events left to deliver
+        let mut truncated = false;
+
+        while let Some(state) = queue.pop_front() {
+            let choices = state.pending.len() + state.wake_ups.len();
+            let in_order = state.in_order();
+            for choice in 0..choices {
+                let delays = state.delays + if Some(choice) == in_order { 0 } else { 1 };
+                if self.delay_bound.map_or(false, |bound| delays > bound) {
+                    continue;
+                }
+                let mut next = match Explorer::apply(&state, choice) {
+                    Some(next) => next,
+                    None => continue,
+                };
+                next.delays = delays;
+                let view = next.nodes.iter().map(Option::as_ref).collect::<Vec<_>>();
+                if let Err(violation) = next.invariant.check(next.time, &view) {
+                    return Exploration {
+                        states,
+                        exhausted: false,
+                        counterexample: Some(Counterexample {
+                            trace: next.trace,
+                            violation,
+                        }),
+                    };
+                }
+                if next.trace.len() >= self.max_depth {
+                    truncated |= !next.pending.is_empty() || next.wake_ups.iter().any(Option::is_some);
+                    continue;
+                }
+                if visited.insert(next.fingerprint()) {
+                    if states == self.max_states {
+                        return Exploration {
+                            states,
+                            exhausted: false,
+                            counterexample: None,
+                        };
+                    }
+                    states += 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        Exploration {
+            states,
+            exhausted: !truncated,
+            counterexample: None,
+        }
+    }
+
+    fn apply<N, C>(
+        state: &State<N, N::Message, N::Input, C>,
+        choice: usize,
+    ) -> Option<State<N, N::Message, N::Input, C>>
+    where
+        N: Node + Clone,
+        N::Input: Clone,
+        C: Clone,
+    {
+        let mut next = state.clone();
+        let (node_id, event) = if choice < next.pending.len() {
+            next.pending.remove(choice)
+        } else {
+            let node_id = choice - next.pending.len();
+            let time = next.wake_ups[node_id].take()?;
+            next.time = next.time.max(time);
+            (node_id, NodeEvent::WakeUp)
+        };
+
+        let node_number = next.nodes.len();
+        let time = next.time;
+        let node = next.nodes[node_id].as_mut()?;
+        let effects = node.handle_event(time, &event);
+        next.trace.push(Step { time, node_id, event });
+        for effect in effects {
+            match effect {
+                Effect::ShutDown => next.nodes[node_id] = None,
+                Effect::SetWakeUpTime(time) => next.wake_ups[node_id] = Some(time),
+                Effect::Broadcast(message) => {
+                    for i in (0..node_number).filter(|&i| i != node_id) {
+                        next.pending.push((i, NodeEvent::MessageReceived(message.clone())));
+                    }
+                },
+                Effect::Send(i, message) => next.pending.push((i, NodeEvent::MessageReceived(message))),
+            }
+        }
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{Node, NodeEvent, Effect, Decisions, LeaderElectionNode, Agreement, Validity};
+    use super::Explorer;
+
+    // nodes 0 and 1 announce themselves, node 2 decides whichever it hears
+    // first, so the decision depends on the schedule, but only one node
+    // ever decides and agreement cannot fail
+    #[derive(Clone, Hash)]
+    struct Race {
+        id: usize,
+        first: Option<usize>,
+    }
+
+    impl Node for Race {
+        type Message = usize;
+        type Input = ();
+
+        fn handle_event(&mut self, time: Duration, event: &NodeEvent<usize>) -> Vec<Effect<usize>> {
+            let _ = time;
+            match *event {
+                NodeEvent::Start { node_id, .. } => {
+                    self.id = node_id;
+                    if node_id < 2 { vec![Effect::Broadcast(node_id)] } else { vec![] }
+                },
+                NodeEvent::MessageReceived(id) if self.id == 2 && self.first.is_none() => {
+                    self.first = Some(id);
+                    vec![]
+                },
+                _ => vec![],
+            }
+        }
+    }
+
+    impl Decisions for Race {
+        type Value = usize;
+
+        fn decided_levels(&self) -> usize {
+            self.first.iter().count()
+        }
+
+        fn decision(&self, level: usize) -> Option<usize> {
+            self.first.filter(|_| level == 0)
+        }
+    }
+
+    #[test]
+    fn branches_do_not_share_decisions() {
+        let nodes = (0..3).map(|_| Race { id: 0, first: None });
+        let exploration = Explorer::new(100, 100_000).explore(nodes, &Agreement::new());
+        assert!(exploration.counterexample.is_none(), "{:?}", exploration.counterexample);
+        assert!(exploration.exhausted);
+    }
+
+    // in order node 0 starts and broadcasts first, node 2 only hears node 1
+    // first if the schedule deviates once
+    #[test]
+    fn delay_bound_limits_the_schedules() {
+        let nodes = || (0..3).map(|_| Race { id: 0, first: None });
+        let first_is_zero = Validity::new(|_, &first: &usize| first == 0);
+        let in_order = Explorer::new(100, 100_000).with_delay_bound(0).explore(nodes(), &first_is_zero);
+        assert!(in_order.exhausted && in_order.counterexample.is_none());
+        let one_delay = Explorer::new(100, 100_000).with_delay_bound(1).explore(nodes(), &first_is_zero);
+        assert!(one_delay.counterexample.is_some());
+    }
+
+    // a node decides once it has started and heard from both others
+    #[test]
+    fn shortest_counterexample() {
+        let nodes = (0..3).map(|_| LeaderElectionNode::good());
+        let exploration = Explorer::new(100, 100_000).explore(nodes, &Validity::new(|_, &leader: &usize| leader != 2));
+        let counterexample = exploration.counterexample.expect("every node elects node 2");
+        assert_eq!(counterexample.violation.invariant, "validity");
+        assert_eq!(counterexample.trace.len(), 5);
+        let starts = counterexample.trace
+            .iter()
+            .filter(|step| matches!(step.event, NodeEvent::Start { .. }))
+            .count();
+        assert_eq!(starts, 3);
+    }
+
+    #[test]
+    fn depth_limit_is_not_exhaustive() {
+        let nodes = || (0..3).map(|_| LeaderElectionNode::good());
+        let exploration = Explorer::new(100, 100_000).explore(nodes(), &Agreement::new());
+        assert!(exploration.exhausted && exploration.counterexample.is_none());
+        let exploration = Explorer::new(4, 100_000).explore(nodes(), &Agreement::new());
+        assert!(!exploration.exhausted && exploration.counterexample.is_none());
+    }
+}
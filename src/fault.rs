@@ -0,0 +1,426 @@
+use std::{cmp::Reverse, ops::Range, time::Duration};
+use super::{node::{Node, Effect}, invariant::Decisions};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Trigger {
+    #[cfg_attr(feature = "serde", serde(rename = "time_ms", with = "millis"))]
+    Time(Duration),
+    // the node itself has decided this many levels
+    Level(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields))]
+pub enum Fault {
+    // the node handles no event at or after `at`
+    Crash {
+        node_id: usize,
+        #[cfg_attr(feature = "serde", serde(rename = "at_ms", with = "millis"))]
+        at: Duration,
+    },
+    // messages sent between the sides within `[from, until)` are lost
+    Partition {
+        left: Vec<usize>,
+        right: Vec<usize>,
+        #[cfg_attr(feature = "serde", serde(rename = "from_ms", with = "millis"))]
+        from: Duration,
+        #[cfg_attr(feature = "serde", serde(rename = "until_ms", with = "millis"))]
+        until: Duration,
+    },
+    // the effects of the node are passed through the adversary
+    Byzantine {
+        node_id: usize,
+        from: Trigger,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct FaultSchedule {
+    faults: Vec<Fault>,
+}
+
+impl FaultSchedule {
+    pub fn new() -> Self {
+        FaultSchedule::default()
+    }
+
+    pub fn faults(&self) -> &[Fault] {
+        &self.faults
+    }
+
+    pub fn with(mut self, fault: Fault) -> Self {
+        self.faults.push(fault);
+        self
+    }
+
+    pub fn crash(self, node_id: usize, at: Duration) -> Self {
+        self.with(Fault::Crash { node_id, at })
+    }
+
+    pub fn partition(self, left: Range<usize>, right: Range<usize>, from: Duration, until: Duration) -> Self {
+        self.with(Fault::Partition {
+            left: left.collect(),
+            right: right.collect(),
+            from,
+            until,
+        })
+    }
+
+    pub fn byzantine(self, node_id: usize, from: Trigger) -> Self {
+        self.with(Fault::Byzantine { node_id, from })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faults.is_empty()
+    }
+}
+
+impl From<Vec<Fault>> for FaultSchedule {
+    fn from(faults: Vec<Fault>) -> Self {
+        FaultSchedule { faults }
+    }
+}
+
+pub trait Adversary<M> {
+    // rewrites the effects the byzantine node produced in reaction to an event
+    fn corrupt(&mut self, time: Duration, node_id: usize, effects: Vec<Effect<M>>) -> Vec<Effect<M>>;
+}
+
+impl<M, F> Adversary<M> for F
+where
+    F: FnMut(Duration, usize, Vec<Effect<M>>) -> Vec<Effect<M>>,
+{
+    fn corrupt(&mut self, time: Duration, node_id: usize, effects: Vec<Effect<M>>) -> Vec<Effect<M>> {
+        self(time, node_id, effects)
+    }
+}
+
+// the default adversary, the node keeps running but sends nothing
+pub struct Silent;
+
+impl<M> Adversary<M> for Silent {
+    fn corrupt(&mut self, time: Duration, node_id: usize, effects: Vec<Effect<M>>) -> Vec<Effect<M>> {
+        let _ = (time, node_id);
+        effects
+            .into_iter()
+            .filter(|effect| !matches!(effect, Effect::Broadcast(_) | Effect::Send(..)))
+            .collect()
+    }
+}
+
+// the schedule as the simulator consumes it
+pub(crate) struct Faults<N>
+where
+    N: Node,
+{
+    // crashes and time triggered byzantine faults, latest first
+    timed: Vec<(Duration, Fault)>,
+    partitions: Vec<(Vec<usize>, Vec<usize>, Duration, Duration)>,
+    levels: Vec<(usize, usize)>,
+    byzantine: Vec<bool>,
+    // the time of the last call to `due`
+    now: Duration,
+    decided_levels: fn(&N) -> usize,
+    adversary: Box<dyn Adversary<N::Message>>,
+}
+
+impl<N> Faults<N>
+where
+    N: Node + Decisions,
+{
+    pub fn new(schedule: FaultSchedule, node_number: usize) -> Self {
+        let mut faults = Faults {
+            timed: vec![],
+            partitions: vec![],
+            levels: vec![],
+            byzantine: vec![false; node_number],
+            now: Duration::from_secs(0),
+            decided_levels: N::decided_levels,
+            adversary: Box::new(Silent),
+        };
+        for fault in schedule.faults {
+            match fault {
+                Fault::Crash { node_id, at } => {
+                    assert!(node_id < node_number, "crash of node {} that doesn't exist", node_id);
+                    faults.timed.push((at, fault));
+                },
+                Fault::Partition { left, right, from, until } => {
+                    faults.partitions.push((left, right, from, until));
+                },
+                Fault::Byzantine { node_id, from } => {
+                    assert!(node_id < node_number, "byzantine node {} doesn't exist", node_id);
+                    match from {
+                        Trigger::Time(at) => faults.timed.push((at, fault)),
+                        Trigger::Level(level) => faults.levels.push((node_id, level)),
+                    }
+                },
+            }
+        }
+        faults.timed.sort_by_key(|&(at, _)| Reverse(at));
+        faults
+    }
+}
+
+impl<N> Faults<N>
+where
+    N: Node,
+{
+    pub fn set_adversary(&mut self, adversary: Box<dyn Adversary<N::Message>>) {
+        self.adversary = adversary;
+    }
+
+    // the faults that are due at `time`, must be called with non decreasing time
+    pub fn due(&mut self, time: Duration) -> Vec<Fault> {
+        assert!(time >= self.now, "faults are due at {:?} after {:?}", time, self.now);
+        self.now = time;
+        let mut due = vec![];
+        while self.timed.last().map(|&(at, _)| at <= time).unwrap_or(false) {
+            let (_, fault) = self.timed.pop().unwrap();
+            if let Fault::Byzantine { node_id, .. } = fault {
+                self.byzantine[node_id] = true;
+            }
+            due.push(fault);
+        }
+        due
+    }
+
+    pub fn check_levels(&mut self, nodes: &[Option<&N>]) {
+        let (byzantine, decided_levels) = (&mut self.byzantine, self.decided_levels);
+        self.levels.retain(|&(node_id, level)| match nodes[node_id] {
+            Some(node) if decided_levels(node) >= level => {
+                byzantine[node_id] = true;
+                false
+            },
+            _ => true,
+        });
+    }
+
+    pub fn corrupt(&mut self, time: Duration, node_id: usize, effects: Vec<Effect<N::Message>>) -> Vec<Effect<N::Message>> {
+        if self.byzantine[node_id] {
+            self.adversary.corrupt(time, node_id, effects)
+        } else {
+            effects
+        }
+    }
+
+    pub fn is_partitioned(&self, time: Duration, a: usize, b: usize) -> bool {
+        self.partitions.iter().any(|(left, right, from, until)| {
+            let split = (left.contains(&a) && right.contains(&b)) || (left.contains(&b) && right.contains(&a));
+            split && *from <= time && time < *until
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{
+        Node, NodeEvent, Effect, Simulator, Observer, Decisions, CaseNetwork, DelayModel,
+        FaultSchedule, Trigger,
+    };
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    // broadcasts `(id, tick)` every second, a tick is a decided level
+    #[derive(Default)]
+    struct Ticker {
+        id: usize,
+        ticks: usize,
+    }
+
+    impl Node for Ticker {
+        type Message = (usize, usize);
+        type Input = ();
+
+        fn handle_event(&mut self, time: Duration, event: &NodeEvent<Self::Message>) -> Vec<Effect<Self::Message>> {
+            match event {
+                NodeEvent::Start { node_id, .. } => self.id = *node_id,
+                NodeEvent::WakeUp => (),
+                _ => return vec![],
+            }
+            self.ticks += 1;
+            vec![Effect::Broadcast((self.id, self.ticks - 1)), Effect::SetWakeUpTime(time + SECOND)]
+        }
+    }
+
+    impl Decisions for Ticker {
+        type Value = usize;
+
+        fn decided_levels(&self) -> usize {
+            self.ticks
+        }
+
+        fn decision(&self, level: usize) -> Option<usize> {
+            Some(level).filter(|&level| level < self.ticks)
+        }
+    }
+
+    // every handled event, and the `(sender, tick)` each node received
+    #[derive(Default)]
+    struct Log {
+        events: Vec<(Duration, usize)>,
+        received: Vec<Vec<(usize, usize)>>,
+    }
+
+    impl Log {
+        fn ticks(&self, receiver: usize, sender: usize) -> Vec<usize> {
+            self.received[receiver]
+                .iter()
+                .filter(|&&(id, _)| id == sender)
+                .map(|&(_, tick)| tick)
+                .collect()
+        }
+    }
+
+    impl Observer<Ticker> for Log {
+        fn on_event(&mut self, time: Duration, _: Duration, node_id: usize, event: &NodeEvent<(usize, usize)>) {
+            self.events.push((time, node_id));
+            self.received.resize(self.received.len().max(node_id + 1), vec![]);
+            if let NodeEvent::MessageReceived(message) = event {
+                self.received[node_id].push(*message);
+            }
+        }
+    }
+
+    fn simulator(faults: FaultSchedule) -> Simulator<Ticker, CaseNetwork> {
+        let network = CaseNetwork::new(DelayModel::Constant(Duration::from_millis(10)), 0);
+        Simulator::new((0..3).map(|_| Ticker::default()), network).with_faults(faults)
+    }
+
+    fn run(simulator: &mut Simulator<Ticker, CaseNetwork>) -> Log {
+        let mut log = Log::default();
+        simulator.run_until(1_000, &mut log, |time, _| time >= 6 * SECOND);
+        log
+    }
+
+    #[test]
+    fn crashed_node_handles_nothing_from_then_on() {
+        let mut simulator = simulator(FaultSchedule::new().crash(1, 2 * SECOND));
+        let log = run(&mut simulator);
+        let handled = log.events.iter().filter(|&&(_, node_id)| node_id == 1).map(|&(time, _)| time);
+        assert_eq!(handled.max(), Some(SECOND + Duration::from_millis(10)));
+        assert!(simulator.nodes().nth(1).unwrap().is_none());
+        // the others keep going
+        assert!(log.events.iter().any(|&(time, node_id)| node_id == 2 && time >= 5 * SECOND));
+    }
+
+    #[test]
+    fn partition_drops_messages_only_inside_the_window() {
+        let mut simulator = simulator(FaultSchedule::new().partition(0..1, 1..3, 2 * SECOND, 4 * SECOND));
+        let log = run(&mut simulator);
+        // ticks 2 and 3 are sent at 2 s and 3 s, within `[from, until)`
+        assert_eq!(log.ticks(1, 0), [0, 1, 4, 5]);
+        assert_eq!(log.ticks(2, 0), [0, 1, 4, 5]);
+        assert_eq!(log.ticks(0, 2), [0, 1, 4, 5]);
+        // the nodes on the same side still talk
+        assert_eq!(log.ticks(2, 1), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn byzantine_from_time() {
+        let mut simulator = simulator(FaultSchedule::new().byzantine(0, Trigger::Time(2 * SECOND)));
+        let log = run(&mut simulator);
+        // silent from 2 s on, but still running
+        assert_eq!(log.ticks(1, 0), [0, 1]);
+        assert_eq!(simulator.nodes().next().unwrap().unwrap().decided_levels(), 7);
+    }
+
+    #[test]
+    fn byzantine_from_level() {
+        let mut simulator = simulator(FaultSchedule::new().byzantine(0, Trigger::Level(3)));
+        let log = run(&mut simulator);
+        // the broadcast of the event that decides the third level is still sent
+        assert_eq!(log.ticks(1, 0), [0, 1, 2]);
+        assert_eq!(log.ticks(2, 0), [0, 1, 2]);
+    }
+
+    #[test]
+    fn adversary_rewrites_the_effects() {
+        let schedule = FaultSchedule::new().byzantine(0, Trigger::Level(2));
+        let mut corrupted = vec![];
+        let mut simulator = simulator(schedule).with_adversary(move |time, node_id, effects: Vec<Effect<_>>| {
+            assert_eq!(node_id, 0);
+            corrupted.push(time);
+            effects
+                .into_iter()
+                .map(|effect| match effect {
+                    Effect::Broadcast((id, tick)) => Effect::Broadcast((id, tick + 100)),
+                    effect => effect,
+                })
+                .collect()
+        });
+        let log = run(&mut simulator);
+        assert_eq!(log.ticks(1, 0), [0, 1, 102, 103, 104, 105]);
+    }
+
+    // asks for a wake up that is already in the past
+    struct Late;
+
+    impl Node for Late {
+        type Message = ();
+        type Input = ();
+
+        fn handle_event(&mut self, _: Duration, event: &NodeEvent<()>) -> Vec<Effect<()>> {
+            match event {
+                NodeEvent::Start { .. } => vec![Effect::SetWakeUpTime(Duration::from_secs(0))],
+                _ => vec![],
+            }
+        }
+    }
+
+    impl Decisions for Late {
+        type Value = ();
+
+        fn decided_levels(&self) -> usize {
+            0
+        }
+
+        fn decision(&self, _: usize) -> Option<()> {
+            None
+        }
+    }
+
+    #[test]
+    fn wake_up_in_the_past_fires_at_once() {
+        struct Times(Vec<Duration>);
+
+        impl Observer<Late> for Times {
+            fn on_event(&mut self, time: Duration, _: Duration, _: usize, _: &NodeEvent<()>) {
+                self.0.push(time);
+            }
+        }
+
+        let network = CaseNetwork::new(DelayModel::Constant(Duration::from_millis(10)), 0);
+        let mut simulator = Simulator::new((0..2).map(|_| Late), network)
+            .with_start_times(vec![SECOND, 2 * SECOND])
+            .with_faults(FaultSchedule::new().crash(1, 3 * SECOND));
+        let mut times = Times(vec![]);
+        simulator.run_observed(10, &mut times);
+        assert_eq!(times.0, [SECOND, SECOND, 2 * SECOND, 2 * SECOND]);
+    }
+}
+
+#[cfg(feature = "serde")]
+mod millis {
+    use std::time::Duration;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    pub fn serialize<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (duration.as_millis() as u64).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u64::deserialize(deserializer).map(Duration::from_millis)
+    }
+}
@@ -0,0 +1,335 @@
+use std::{ops::RangeInclusive, time::Duration};
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::index};
+use super::{
+    node::Node,
+    network::{Network, mix},
+    simulator::Simulator,
+    invariant::{Invariant, Violation},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DelayModel {
+    Constant(Duration),
+    Uniform {
+        min: Duration,
+        max: Duration,
+    },
+    Exponential {
+        mean: Duration,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub seed: u64,
+    pub node_number: usize,
+    // crashed from the start
+    pub faulty: Vec<usize>,
+    pub delay: DelayModel,
+}
+
+#[derive(Debug)]
+pub struct Failure {
+    pub case: Case,
+    pub violation: Violation,
+}
+
+pub struct CaseNetwork {
+    delay: DelayModel,
+    seed: u64,
+}
+
+impl CaseNetwork {
+    pub fn new(delay: DelayModel, seed: u64) -> Self {
+        CaseNetwork { delay, seed }
+    }
+}
+
+impl<M> Network<M> for CaseNetwork {
+    fn delay(
+        &mut self,
+        this: Duration,
+        iteration: usize,
+        effect_index: usize,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &M,
+    ) -> Duration {
+        let _ = (this, message);
+        let r = mix(&[
+            self.seed,
+            iteration as u64,
+            effect_index as u64,
+            sender_node_id as u64,
+            receiver_node_id as u64,
+        ]);
+        // uniform in [0, 1)
+        let u = (r >> 11) as f64 / (1u64 << 53) as f64;
+        match self.delay {
+            DelayModel::Constant(delay) => delay,
+            DelayModel::Uniform { min, max } => min + (max - min).mul_f64(u),
+            DelayModel::Exponential { mean } => mean.mul_f64(-(1.0 - u).ln()),
+        }
+    }
+}
+
+// generates random cases, runs each of them with the invariant and shrinks
+// the first failing case, `check` panics so it can be called from `#[test]`
+pub struct Harness {
+    seed: u64,
+    cases: usize,
+    node_number: RangeInclusive<usize>,
+    max_faulty: fn(usize) -> usize,
+    max_delay: Duration,
+    iterations: usize,
+}
+
+impl Harness {
+    pub fn new(seed: u64) -> Self {
+        Harness {
+            seed,
+            cases: 100,
+            node_number: 1..=10,
+            max_faulty: |node_number| (node_number - 1) / 3,
+            max_delay: Duration::from_millis(500),
+            iterations: 10_000,
+        }
+    }
+
+    pub fn cases(self, cases: usize) -> Self {
+        Harness { cases, ..self }
+    }
+
+    pub fn node_number(self, node_number: RangeInclusive<usize>) -> Self {
+        assert!(*node_number.start() > 0);
+        Harness { node_number, ..self }
+    }
+
+    pub fn max_faulty(self, max_faulty: fn(usize) -> usize) -> Self {
+        Harness { max_faulty, ..self }
+    }
+
+    pub fn max_delay(self, max_delay: Duration) -> Self {
+        Harness { max_delay, ..self }
+    }
+
+    pub fn iterations(self, iterations: usize) -> Self {
+        Harness { iterations, ..self }
+    }
+
+    pub fn generate(&self) -> impl Iterator<Item = Case> {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let node_number = self.node_number.clone();
+        let max_faulty = self.max_faulty;
+        let max_delay = self.max_delay;
+        (0..self.cases).map(move |_| {
+            let seed = rng.gen();
+            let node_number = rng.gen_range(node_number.clone());
+            let faulty_number = rng.gen_range(0..=max_faulty(node_number).min(node_number));
+            let mut faulty = index::sample(&mut rng, node_number, faulty_number).into_vec();
+            faulty.sort_unstable();
+            let kind = rng.gen_range(0..3);
+            let mut random_delay = || max_delay.mul_f64(rng.gen());
+            let delay = match kind {
+                0 => DelayModel::Constant(random_delay()),
+                1 => {
+                    let (a, b) = (random_delay(), random_delay());
+                    DelayModel::Uniform { min: a.min(b), max: a.max(b) }
+                },
+                _ => DelayModel::Exponential { mean: random_delay() / 4 },
+            };
+            Case {
+                seed,
+                node_number,
+                faulty,
+                delay,
+            }
+        })
+    }
+
+    pub fn run_case<N, I>(
+        &self,
+        case: &Case,
+        make_nodes: impl Fn(&Case) -> Vec<N>,
+        make_invariant: impl Fn(&Case) -> I,
+    ) -> Result<(), Violation>
+    where
+        N: Node,
+        N::Message: std::fmt::Debug,
+        N::Input: std::fmt::Debug,
+        I: Invariant<N>,
+    {
+        let network = CaseNetwork::new(case.delay.clone(), case.seed);
+        let mut simulator = Simulator::new(make_nodes(case).into_iter(), network);
+        for &node_id in &case.faulty {
+            simulator.shut_down(node_id);
+        }
+        simulator.run_checked(self.iterations, &mut (), &mut make_invariant(case))
+    }
+
+    #[allow(clippy::result_large_err)]
+    pub fn run<N, I>(
+        &self,
+        make_nodes: impl Fn(&Case) -> Vec<N>,
+        make_invariant: impl Fn(&Case) -> I,
+    ) -> Result<(), Failure>
+    where
+        N: Node,
+        N::Message: std::fmt::Debug,
+        N::Input: std::fmt::Debug,
+        I: Invariant<N>,
+    {
+        for case in self.generate() {
+            if let Err(violation) = self.run_case(&case, &make_nodes, &make_invariant) {
+                return Err(self.shrink(Failure { case, violation }, &make_nodes, &make_invariant));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check<N, I>(
+        &self,
+        make_nodes: impl Fn(&Case) -> Vec<N>,
+        make_invariant: impl Fn(&Case) -> I,
+    )
+    where
+        N: Node,
+        N::Message: std::fmt::Debug,
+        N::Input: std::fmt::Debug,
+        I: Invariant<N>,
+    {
+        if let Err(failure) = self.run(make_nodes, make_invariant) {
+            panic!("{}\nminimal failing case: {:?}", failure.violation, failure.case);
+        }
+    }
+
+    fn shrink<N, I>(
+        &self,
+        mut failure: Failure,
+        make_nodes: impl Fn(&Case) -> Vec<N>,
+        make_invariant: impl Fn(&Case) -> I,
+    ) -> Failure
+    where
+        N: Node,
+        N::Message: std::fmt::Debug,
+        N::Input: std::fmt::Debug,
+        I: Invariant<N>,
+    {
+        'outer: loop {
+            for candidate in self.simplifications(&failure.case) {
+                if let Err(violation) = self.run_case(&candidate, &make_nodes, &make_invariant) {
+                    failure = Failure {
+                        case: candidate,
+                        violation,
+                    };
+                    continue 'outer;
+                }
+            }
+            break failure;
+        }
+    }
+
+    fn simplifications(&self, case: &Case) -> Vec<Case> {
+        let mut candidates = vec![];
+
+        if case.node_number > *self.node_number.start() {
+            let node_number = case.node_number - 1;
+            let mut faulty = case.faulty
+                .iter()
+                .cloned()
+                .filter(|&node_id| node_id < node_number)
+                .collect::<Vec<_>>();
+            faulty.truncate((self.max_faulty)(node_number));
+            candidates.push(Case {
+                node_number,
+                faulty,
+                ..case.clone()
+            });
+        }
+
+        for i in 0..case.faulty.len() {
+            let mut faulty = case.faulty.clone();
+            faulty.remove(i);
+            candidates.push(Case {
+                faulty,
+                ..case.clone()
+            });
+        }
+
+        let simpler_delays = match case.delay {
+            DelayModel::Constant(delay) if delay > Duration::from_millis(1) => {
+                vec![DelayModel::Constant(delay / 2)]
+            },
+            DelayModel::Constant(_) => vec![],
+            DelayModel::Uniform { min, max } => {
+                vec![DelayModel::Constant(min), DelayModel::Constant(max)]
+            },
+            DelayModel::Exponential { mean } => vec![DelayModel::Constant(mean)],
+        };
+        for delay in simpler_delays {
+            candidates.push(Case {
+                delay,
+                ..case.clone()
+            });
+        }
+
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{
+        Agreement, Validity, LeaderElectionNode, BenOrNode, BenOrConfig, StreamletNode,
+        StreamletConfig,
+    };
+    use super::{Harness, DelayModel};
+
+    #[test]
+    fn leader_election_agrees() {
+        Harness::new(0)
+            .cases(50)
+            .check(|case| vec![LeaderElectionNode::good(); case.node_number], |_| Agreement::new());
+    }
+
+    #[test]
+    fn ben_or_agrees() {
+        Harness::new(1).cases(50).check(
+            |case| BenOrConfig::new(case.node_number, case.faulty.len(), case.seed).map(BenOrNode::new).collect(),
+            |_| Agreement::new(),
+        );
+    }
+
+    #[test]
+    fn streamlet_agrees() {
+        Harness::new(2).cases(20).iterations(2_000).check(
+            |case| StreamletConfig::new(case.node_number, Duration::from_secs(1)).map(StreamletNode::new).collect(),
+            |_| Agreement::new(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "minimal failing case")]
+    fn check_panics_on_violation() {
+        Harness::new(3)
+            .cases(10)
+            .check(|case| vec![LeaderElectionNode::good(); case.node_number], |_| {
+                Validity::new(|_, leader: &usize| *leader == 0)
+            });
+    }
+
+    #[test]
+    fn failure_is_shrunk() {
+        let failure = Harness::new(3)
+            .cases(10)
+            .run(|case| vec![LeaderElectionNode::good(); case.node_number], |_| {
+                Validity::new(|_, leader: &usize| *leader == 0)
+            })
+            .unwrap_err();
+        // a single node elects itself, two are needed for another leader
+        assert_eq!(failure.case.node_number, 2);
+        assert!(failure.case.faulty.is_empty());
+        assert!(matches!(failure.case.delay, DelayModel::Constant(delay) if delay <= Duration::from_millis(1)));
+    }
+}
@@ -0,0 +1,210 @@
+use std::{fmt, time::Duration};
+
+#[derive(Debug, Clone)]
+pub struct Violation {
+    pub invariant: &'static str,
+    pub time: Duration,
+    pub description: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} violated at {:?}: {}", self.invariant, self.time, self.description)
+    }
+}
+
+// evaluated after every step, shut down nodes are `None`
+pub trait Invariant<N> {
+    fn check(&mut self, time: Duration, nodes: &[Option<&N>]) -> Result<(), Violation>;
+}
+
+impl<N, F> Invariant<N> for F
+where
+    F: FnMut(Duration, &[Option<&N>]) -> Result<(), Violation>,
+{
+    fn check(&mut self, time: Duration, nodes: &[Option<&N>]) -> Result<(), Violation> {
+        self(time, nodes)
+    }
+}
+
+impl<N, A, B> Invariant<N> for (A, B)
+where
+    A: Invariant<N>,
+    B: Invariant<N>,
+{
+    fn check(&mut self, time: Duration, nodes: &[Option<&N>]) -> Result<(), Violation> {
+        self.0.check(time, nodes)?;
+        self.1.check(time, nodes)
+    }
+}
+
+impl<N, A, B, C> Invariant<N> for (A, B, C)
+where
+    A: Invariant<N>,
+    B: Invariant<N>,
+    C: Invariant<N>,
+{
+    fn check(&mut self, time: Duration, nodes: &[Option<&N>]) -> Result<(), Violation> {
+        self.0.check(time, nodes)?;
+        self.1.check(time, nodes)?;
+        self.2.check(time, nodes)
+    }
+}
+
+impl<N> Invariant<N> for Vec<Box<dyn Invariant<N>>> {
+    fn check(&mut self, time: Duration, nodes: &[Option<&N>]) -> Result<(), Violation> {
+        self.iter_mut().try_for_each(|invariant| invariant.check(time, nodes))
+    }
+}
+
+// what a node has irrevocably decided, levels are numbered from zero
+// and a node decides them in order
+pub trait Decisions {
+    type Value: Clone + PartialEq + fmt::Debug;
+
+    fn decided_levels(&self) -> usize;
+
+    fn decision(&self, level: usize) -> Option<Self::Value>;
+}
+
+// no two nodes decide different values at the same level
+#[derive(Clone)]
+pub struct Agreement<V> {
+    decided: Vec<(V, usize)>,
+    checked: Vec<usize>,
+}
+
+impl<V> Agreement<V> {
+    pub fn new() -> Self {
+        Agreement {
+            decided: vec![],
+            checked: vec![],
+        }
+    }
+}
+
+impl<V> Default for Agreement<V> {
+    fn default() -> Self {
+        Agreement::new()
+    }
+}
+
+impl<N> Invariant<N> for Agreement<N::Value>
+where
+    N: Decisions,
+{
+    fn check(&mut self, time: Duration, nodes: &[Option<&N>]) -> Result<(), Violation> {
+        self.checked.resize(nodes.len(), 0);
+        for (node_id, node) in nodes.iter().enumerate() {
+            let node = match node {
+                Some(node) => node,
+                None => continue,
+            };
+            for level in self.checked[node_id]..node.decided_levels() {
+                let value = node.decision(level).expect("decided level must have a value");
+                match self.decided.get(level) {
+                    None => self.decided.push((value, node_id)),
+                    Some((other, other_id)) if *other != value => {
+                        return Err(Violation {
+                            invariant: "agreement",
+                            time,
+                            description: format!(
+                                "level {}: node {} decided {:?}, node {} decided {:?}",
+                                level, other_id, other, node_id, value,
+                            ),
+                        });
+                    },
+                    Some(_) => (),
+                }
+            }
+            self.checked[node_id] = node.decided_levels();
+        }
+        Ok(())
+    }
+}
+
+// every decided value satisfies the predicate
+#[derive(Clone)]
+pub struct Validity<P> {
+    is_valid: P,
+    checked: Vec<usize>,
+}
+
+impl<P> Validity<P> {
+    pub fn new(is_valid: P) -> Self {
+        Validity {
+            is_valid,
+            checked: vec![],
+        }
+    }
+}
+
+impl<N, P> Invariant<N> for Validity<P>
+where
+    N: Decisions,
+    P: FnMut(usize, &N::Value) -> bool,
+{
+    fn check(&mut self, time: Duration, nodes: &[Option<&N>]) -> Result<(), Violation> {
+        self.checked.resize(nodes.len(), 0);
+        for (node_id, node) in nodes.iter().enumerate() {
+            let node = match node {
+                Some(node) => node,
+                None => continue,
+            };
+            for level in self.checked[node_id]..node.decided_levels() {
+                let value = node.decision(level).expect("decided level must have a value");
+                if !(self.is_valid)(level, &value) {
+                    return Err(Violation {
+                        invariant: "validity",
+                        time,
+                        description: format!("level {}: node {} decided {:?}", level, node_id, value),
+                    });
+                }
+            }
+            self.checked[node_id] = node.decided_levels();
+        }
+        Ok(())
+    }
+}
+
+// by `deadline` every running node has decided at least `levels` levels
+#[derive(Clone)]
+pub struct Termination {
+    deadline: Duration,
+    levels: usize,
+}
+
+impl Termination {
+    pub fn new(deadline: Duration, levels: usize) -> Self {
+        Termination {
+            deadline,
+            levels,
+        }
+    }
+}
+
+impl<N> Invariant<N> for Termination
+where
+    N: Decisions,
+{
+    fn check(&mut self, time: Duration, nodes: &[Option<&N>]) -> Result<(), Violation> {
+        if time < self.deadline {
+            return Ok(());
+        }
+        for (node_id, node) in nodes.iter().enumerate() {
+            if let Some(node) = node {
+                if node.decided_levels() < self.levels {
+                    return Err(Violation {
+                        invariant: "termination",
+                        time,
+                        description: format!(
+                            "node {} decided {} of {} levels by {:?}",
+                            node_id, node.decided_levels(), self.levels, self.deadline,
+                        ),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -7,13 +7,58 @@ mod event;
 
 mod node_state;
 
+mod clock;
+pub use self::clock::{Clock, random_clocks};
+
 mod network;
-pub use self::network::Network;
+pub use self::network::{Network, MessageSize, MatrixNetwork, MatrixError, BandwidthNetwork, FifoNetwork, mix};
+pub use self::network::{AdversarialNetwork, Delivery, slow_senders, split_late};
+
+mod overlay;
+pub use self::overlay::Overlay;
 
 mod simulator;
 pub use self::simulator::Simulator;
 
+mod start;
+pub use self::start::random_start_times;
+
+mod workload;
+pub use self::workload::{Arrival, steady, bursty};
+
+mod invariant;
+pub use self::invariant::{Invariant, Violation, Decisions, Agreement, Validity, Termination};
+
+mod fault;
+pub use self::fault::{Fault, FaultSchedule, Trigger, Adversary, Silent};
+
+mod observer;
+pub use self::observer::Observer;
+
+mod metrics;
+pub use self::metrics::{Metrics, MessageKind, SyncTimes, LevelMetrics, Summary, Traffic};
+
+mod batch;
+pub use self::batch::{Batch, Run, aggregate, grid};
+
+mod harness;
+pub use self::harness::{Harness, Case, DelayModel, CaseNetwork, Failure};
+
+mod explorer;
+pub use self::explorer::{Explorer, Exploration, Counterexample, Step};
+
+mod trace;
+pub use self::trace::{Trace, replay};
+
 mod algorithms;
 pub use self::algorithms::leader_election::LeaderElectionNode;
+pub use self::algorithms::ben_or::{BenOrNode, BenOrConfig};
+pub use self::algorithms::streamlet::{StreamletNode, StreamletConfig};
+#[cfg(feature = "tenderbake")]
+pub use self::algorithms::tenderbake::{TenderbakeNode, TenderbakeConfig, TenderbakeMessage, TenderbakeTransaction, Mempool};
+#[cfg(feature = "tenderbake")]
+pub use self::algorithms::tenderbake::{Ledger, TransferError, StateAgreement, BlockTree};
+#[cfg(feature = "tenderbake")]
+pub use self::algorithms::tenderbake::{TenderbakeBlock, TenderbakeBlockHash, TenderbakeRound, FinalityHook, Finalized};
 #[cfg(feature = "tenderbake")]
-pub use self::algorithms::tenderbake::{TenderbakeNode, TenderbakeConfig};
+pub use self::algorithms::tenderbake::TenderbakeDecodeError;
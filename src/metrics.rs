@@ -0,0 +1,347 @@
+use std::{collections::BTreeMap, fmt, io, time::Duration};
+use super::{
+    node::{Node, NodeEvent},
+    network::MessageSize,
+    invariant::Decisions,
+    observer::Observer,
+};
+
+pub trait MessageKind {
+    // the value a proposal puts forward, matches `Decisions::Value`
+    type Proposal;
+
+    fn kind(&self) -> &'static str;
+
+    // the proposed value and the round of the proposal
+    fn proposal(&self) -> Option<(Self::Proposal, u64)> {
+        None
+    }
+}
+
+// nodes that fetch the levels decided without them from others
+pub trait SyncTimes {
+    // how long each catching up took
+    fn sync_times(&self) -> &[Duration];
+}
+
+impl MessageKind for usize {
+    type Proposal = usize;
+
+    fn kind(&self) -> &'static str {
+        "id"
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LevelMetrics {
+    pub level: usize,
+    pub proposed_at: Option<Duration>,
+    // the first node to decide the level
+    pub finalized_at: Duration,
+    // zero based round in which the decided value was proposed
+    pub round: Option<u64>,
+}
+
+impl LevelMetrics {
+    pub fn latency(&self) -> Option<Duration> {
+        self.proposed_at.map(|proposed_at| self.finalized_at.saturating_sub(proposed_at))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl Summary {
+    pub fn new(mut samples: Vec<f64>) -> Self {
+        if samples.is_empty() {
+            return Summary::default();
+        }
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        // nearest rank percentile
+        let percentile = |p: f64| {
+            let rank = (p * samples.len() as f64).ceil() as usize;
+            samples[rank.max(1) - 1]
+        };
+        Summary {
+            count: samples.len(),
+            min: samples[0],
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            max: samples[samples.len() - 1],
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "n={} min={:.3} p50={:.3} p90={:.3} p99={:.3} max={:.3} mean={:.3}",
+            self.count, self.min, self.p50, self.p90, self.p99, self.max, self.mean,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Traffic {
+    pub messages: usize,
+    pub bytes: usize,
+}
+
+pub struct Metrics<V> {
+    proposals: Vec<(V, Duration, u64)>,
+    levels: Vec<LevelMetrics>,
+    decided: Vec<usize>,
+    // catching up times of every node
+    sync_times: Vec<Duration>,
+    traffic: BTreeMap<&'static str, Traffic>,
+    events: Vec<usize>,
+    end_time: Duration,
+}
+
+impl<V> Default for Metrics<V> {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl<V> Metrics<V> {
+    pub fn new() -> Self {
+        Metrics {
+            proposals: vec![],
+            levels: vec![],
+            decided: vec![],
+            sync_times: vec![],
+            traffic: BTreeMap::new(),
+            events: vec![],
+            end_time: Duration::default(),
+        }
+    }
+
+    // combines the metrics of independent runs, levels are appended and
+    // the simulated time adds up, so the throughput is the overall one
+    pub fn merge(&mut self, other: Self) {
+        self.levels.extend(other.levels);
+        self.sync_times.extend(other.sync_times);
+        for (kind, traffic) in other.traffic {
+            let t = self.traffic.entry(kind).or_default();
+            t.messages += traffic.messages;
+            t.bytes += traffic.bytes;
+        }
+        if self.events.len() < other.events.len() {
+            self.events.resize(other.events.len(), 0);
+        }
+        for (a, b) in self.events.iter_mut().zip(other.events) {
+            *a += b;
+        }
+        self.end_time += other.end_time;
+    }
+
+    pub fn levels(&self) -> &[LevelMetrics] {
+        &self.levels
+    }
+
+    pub fn traffic(&self) -> &BTreeMap<&'static str, Traffic> {
+        &self.traffic
+    }
+
+    pub fn total_traffic(&self) -> Traffic {
+        self.traffic.values().fold(Traffic::default(), |acc, t| Traffic {
+            messages: acc.messages + t.messages,
+            bytes: acc.bytes + t.bytes,
+        })
+    }
+
+    // number of events each node has handled
+    pub fn events(&self) -> &[usize] {
+        &self.events
+    }
+
+    pub fn end_time(&self) -> Duration {
+        self.end_time
+    }
+
+    // finalized levels per second of simulated time
+    pub fn throughput(&self) -> f64 {
+        if self.end_time == Duration::default() {
+            0.0
+        } else {
+            self.levels.len() as f64 / self.end_time.as_secs_f64()
+        }
+    }
+
+    // seconds from the first proposal to the first decision
+    pub fn latency_summary(&self) -> Summary {
+        Summary::new(self.levels.iter().filter_map(LevelMetrics::latency).map(|d| d.as_secs_f64()).collect())
+    }
+
+    // number of rounds needed per level
+    pub fn rounds_summary(&self) -> Summary {
+        Summary::new(self.levels.iter().filter_map(|l| l.round).map(|r| (r + 1) as f64).collect())
+    }
+
+    // the catching up the nodes reported, call it once the run is over
+    pub fn record_sync_times<'a, N>(&mut self, nodes: impl IntoIterator<Item = Option<&'a N>>)
+    where
+        N: SyncTimes + 'a,
+    {
+        for node in nodes.into_iter().flatten() {
+            self.sync_times.extend_from_slice(node.sync_times());
+        }
+    }
+
+    // seconds a lagging node took to catch up
+    pub fn sync_summary(&self) -> Summary {
+        Summary::new(self.sync_times.iter().map(Duration::as_secs_f64).collect())
+    }
+
+    pub fn write_csv(&self, mut w: impl io::Write) -> io::Result<()> {
+        writeln!(w, "level,proposed_at,finalized_at,latency,round")?;
+        let secs = |d: Option<Duration>| d.map(|d| d.as_secs_f64().to_string()).unwrap_or_default();
+        for l in &self.levels {
+            writeln!(
+                w,
+                "{},{},{},{},{}",
+                l.level,
+                secs(l.proposed_at),
+                l.finalized_at.as_secs_f64(),
+                secs(l.latency()),
+                l.round.map(|r| r.to_string()).unwrap_or_default(),
+            )?;
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    pub fn write_json(&self, mut w: impl io::Write) -> io::Result<()> {
+        serde_json::to_writer(&mut w, self)?;
+        writeln!(w)
+    }
+}
+
+// the totals of a run, times in seconds
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct Report<'a> {
+    end_time: f64,
+    finalized_levels: usize,
+    throughput: f64,
+    latency: Summary,
+    rounds: Summary,
+    sync: Summary,
+    traffic: &'a BTreeMap<&'static str, Traffic>,
+    events: &'a [usize],
+}
+
+#[cfg(feature = "serde")]
+impl<V> serde::Serialize for Metrics<V> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let report = Report {
+            end_time: self.end_time.as_secs_f64(),
+            finalized_levels: self.levels.len(),
+            throughput: self.throughput(),
+            latency: self.latency_summary(),
+            rounds: self.rounds_summary(),
+            sync: self.sync_summary(),
+            traffic: &self.traffic,
+            events: &self.events,
+        };
+        report.serialize(serializer)
+    }
+}
+
+impl<N> Observer<N> for Metrics<N::Value>
+where
+    N: Node + Decisions,
+    N::Message: MessageKind<Proposal = N::Value> + MessageSize,
+{
+    fn on_event(
+        &mut self,
+        time: Duration,
+        local_time: Duration,
+        node_id: usize,
+        event: &NodeEvent<N::Message, N::Input>,
+    ) {
+        let _ = (local_time, event);
+        if self.events.len() <= node_id {
+            self.events.resize(node_id + 1, 0);
+        }
+        self.events[node_id] += 1;
+        self.end_time = self.end_time.max(time);
+    }
+
+    fn on_send(
+        &mut self,
+        time: Duration,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &N::Message,
+    ) {
+        let _ = (sender_node_id, receiver_node_id);
+        let traffic = self.traffic.entry(message.kind()).or_default();
+        traffic.messages += 1;
+        traffic.bytes += message.size();
+        if let Some((value, round)) = message.proposal() {
+            if self.proposals.iter().all(|(v, _, _)| *v != value) {
+                self.proposals.push((value, time, round));
+            }
+        }
+    }
+
+    fn on_step(&mut self, time: Duration, nodes: &[Option<&N>]) {
+        self.decided.resize(nodes.len(), 0);
+        for (node_id, node) in nodes.iter().enumerate() {
+            let node = match node {
+                Some(node) => node,
+                None => continue,
+            };
+            let decided_levels = node.decided_levels();
+            for level in self.levels.len().max(self.decided[node_id])..decided_levels {
+                let value = node.decision(level).expect("decided level must have a value");
+                let proposal = self.proposals.iter().find(|(v, _, _)| *v == value);
+                self.levels.push(LevelMetrics {
+                    level,
+                    proposed_at: proposal.map(|&(_, t, _)| t),
+                    finalized_at: time,
+                    round: proposal.map(|&(_, _, r)| r),
+                });
+            }
+            self.decided[node_id] = decided_levels;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{Simulator, CaseNetwork, DelayModel, LeaderElectionNode, Agreement};
+    use super::Metrics;
+
+    #[test]
+    fn metrics_and_invariant_in_one_run() {
+        let network = CaseNetwork::new(DelayModel::Constant(Duration::from_millis(10)), 0);
+        let mut simulator = Simulator::new((0..4).map(|_| LeaderElectionNode::good()), network);
+        let mut metrics = Metrics::new();
+        simulator
+            .run_checked(1_000, &mut metrics, &mut Agreement::new())
+            .unwrap();
+        assert_eq!(metrics.levels().len(), 1);
+        assert_eq!(metrics.traffic()["id"].messages, 4 * 3);
+        assert_eq!(metrics.events(), &[4, 4, 4, 4]);
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+use super::Network;
+
+pub struct Delivery<'a, M> {
+    pub time: Duration,
+    pub sender_node_id: usize,
+    pub receiver_node_id: usize,
+    pub message: &'a M,
+}
+
+// the strategy chooses any delay, the network enforces partial synchrony:
+// a message sent at `t` is delivered by `max(t, gst) + bound`
+pub struct AdversarialNetwork<F> {
+    gst: Duration,
+    bound: Duration,
+    strategy: F,
+}
+
+impl<F> AdversarialNetwork<F> {
+    pub fn new(gst: Duration, bound: Duration, strategy: F) -> Self {
+        AdversarialNetwork {
+            gst,
+            bound,
+            strategy,
+        }
+    }
+}
+
+impl<F, M> Network<M> for AdversarialNetwork<F>
+where
+    F: FnMut(&Delivery<M>) -> Duration,
+{
+    fn delay(
+        &mut self,
+        this: Duration,
+        iteration: usize,
+        effect_index: usize,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &M,
+    ) -> Duration {
+        let _ = (iteration, effect_index);
+        let delay = (self.strategy)(&Delivery {
+            time: this,
+            sender_node_id,
+            receiver_node_id,
+            message,
+        });
+        let deadline = this.max(self.gst) + self.bound;
+        delay.min(deadline - this)
+    }
+}
+
+// messages of the given senders take as long as allowed, the rest take `fast`
+pub fn slow_senders<M>(
+    senders: Vec<usize>,
+    fast: Duration,
+) -> impl FnMut(&Delivery<M>) -> Duration {
+    move |delivery| {
+        if senders.contains(&delivery.sender_node_id) {
+            Duration::MAX
+        } else {
+            fast
+        }
+    }
+}
+
+// messages matching `is_target` reach the first half of the nodes as late as
+// allowed and the other half after `fast`, everything else takes `fast`
+pub fn split_late<M, P>(
+    node_number: usize,
+    fast: Duration,
+    is_target: P,
+) -> impl FnMut(&Delivery<M>) -> Duration
+where
+    P: Fn(&M) -> bool,
+{
+    move |delivery| {
+        if is_target(delivery.message) && delivery.receiver_node_id < node_number / 2 {
+            Duration::MAX
+        } else {
+            fast
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::Network;
+    use super::{AdversarialNetwork, Delivery, slow_senders};
+
+    const GST: Duration = Duration::from_secs(10);
+    const BOUND: Duration = Duration::from_secs(1);
+
+    fn delay<F>(network: &mut AdversarialNetwork<F>, this: Duration, sender_node_id: usize) -> Duration
+    where
+        F: FnMut(&Delivery<()>) -> Duration,
+    {
+        network.delay(this, 0, 0, sender_node_id, 1, &())
+    }
+
+    #[test]
+    fn delay_is_clamped_to_the_bound_after_gst() {
+        let secs = Duration::from_secs;
+        let mut network = AdversarialNetwork::new(GST, BOUND, |_: &Delivery<()>| Duration::MAX);
+        assert_eq!(delay(&mut network, secs(0), 0), GST + BOUND);
+        assert_eq!(delay(&mut network, secs(2), 0), secs(9));
+        assert_eq!(delay(&mut network, GST, 0), BOUND);
+        assert_eq!(delay(&mut network, secs(12), 0), BOUND);
+    }
+
+    #[test]
+    fn shorter_delay_is_kept() {
+        let fast = Duration::from_millis(5);
+        let mut network = AdversarialNetwork::new(GST, BOUND, slow_senders(vec![0], fast));
+        assert_eq!(delay(&mut network, Duration::from_secs(2), 1), fast);
+        assert_eq!(delay(&mut network, Duration::from_secs(12), 1), fast);
+        assert_eq!(delay(&mut network, Duration::from_secs(2), 0), Duration::from_secs(9));
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+use super::{Network, MessageSize};
+
+// wraps a latency model, every copy of a message first waits in the sender's
+// upload queue and is then transmitted at the speed of the slowest of the
+// sender's uplink and the link to the receiver
+pub struct BandwidthNetwork<D> {
+    inner: D,
+    // bytes per second
+    upload: Vec<u64>,
+    links: Vec<Vec<u64>>,
+    busy_until: Vec<Duration>,
+}
+
+impl<D> BandwidthNetwork<D> {
+    pub fn new(inner: D, upload: Vec<u64>, links: Vec<Vec<u64>>) -> Self {
+        let node_number = upload.len();
+        assert_eq!(links.len(), node_number);
+        assert!(links.iter().all(|row| row.len() == node_number));
+        assert!(upload.iter().chain(links.iter().flatten()).all(|&b| b > 0));
+        BandwidthNetwork {
+            inner,
+            upload,
+            links,
+            busy_until: vec![Duration::default(); node_number],
+        }
+    }
+
+    pub fn uniform(inner: D, node_number: usize, upload: u64, link: u64) -> Self {
+        let links = vec![vec![link; node_number]; node_number];
+        BandwidthNetwork::new(inner, vec![upload; node_number], links)
+    }
+}
+
+impl<D, M> Network<M> for BandwidthNetwork<D>
+where
+    D: Network<M>,
+    M: MessageSize,
+{
+    fn delay(
+        &mut self,
+        this: Duration,
+        iteration: usize,
+        effect_index: usize,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &M,
+    ) -> Duration {
+        let bandwidth = self.upload[sender_node_id].min(self.links[sender_node_id][receiver_node_id]);
+        let transmission = Duration::from_secs_f64(message.size() as f64 / bandwidth as f64);
+        let start = this.max(self.busy_until[sender_node_id]);
+        self.busy_until[sender_node_id] = start + transmission;
+        let propagation = self.inner.delay(
+            this,
+            iteration,
+            effect_index,
+            sender_node_id,
+            receiver_node_id,
+            message,
+        );
+        (start - this) + transmission + propagation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{Network, CaseNetwork, DelayModel};
+    use super::BandwidthNetwork;
+
+    const PROPAGATION: Duration = Duration::from_millis(5);
+
+    fn network(upload: u64, link: u64) -> BandwidthNetwork<CaseNetwork> {
+        let inner = CaseNetwork::new(DelayModel::Constant(PROPAGATION), 0);
+        BandwidthNetwork::uniform(inner, 4, upload, link)
+    }
+
+    // an id takes 8 bytes, 8 ms at 1000 bytes per second
+    #[test]
+    fn copies_of_a_broadcast_wait_in_the_upload_queue() {
+        let mut network = network(1_000, 1_000_000);
+        let this = Duration::from_secs(1);
+        let transmission = Duration::from_millis(8);
+        let delays = (1..4)
+            .map(|receiver| network.delay(this, 0, 0, 0, receiver, &0usize))
+            .collect::<Vec<_>>();
+        assert_eq!(delays, (1..4).map(|k| transmission * k + PROPAGATION).collect::<Vec<_>>());
+
+        // another sender has its own queue
+        assert_eq!(network.delay(this, 0, 1, 1, 0, &0usize), transmission + PROPAGATION);
+        // the queue has drained by the time the next message is sent
+        assert_eq!(network.delay(this + transmission * 3, 1, 0, 0, 1, &0usize), transmission + PROPAGATION);
+    }
+
+    #[test]
+    fn slowest_of_upload_and_link() {
+        let mut network = network(1_000_000, 1_000);
+        let delay = network.delay(Duration::default(), 0, 0, 2, 3, &0usize);
+        assert_eq!(delay, Duration::from_millis(8) + PROPAGATION);
+    }
+}
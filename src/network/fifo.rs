@@ -0,0 +1,83 @@
+use std::{collections::BTreeMap, time::Duration};
+use super::Network;
+
+// TCP-like links, a message is never delivered before an earlier message
+// sent over the same sender -> receiver link
+pub struct FifoNetwork<D> {
+    inner: D,
+    last_delivery: BTreeMap<(usize, usize), Duration>,
+}
+
+impl<D> FifoNetwork<D> {
+    pub fn new(inner: D) -> Self {
+        FifoNetwork {
+            inner,
+            last_delivery: BTreeMap::new(),
+        }
+    }
+}
+
+impl<D, M> Network<M> for FifoNetwork<D>
+where
+    D: Network<M>,
+{
+    fn delay(
+        &mut self,
+        this: Duration,
+        iteration: usize,
+        effect_index: usize,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &M,
+    ) -> Duration {
+        let delay = self.inner.delay(
+            this,
+            iteration,
+            effect_index,
+            sender_node_id,
+            receiver_node_id,
+            message,
+        );
+        let last = self.last_delivery
+            .entry((sender_node_id, receiver_node_id))
+            .or_default();
+        // strictly later, so the order does not depend on how ties are broken
+        let delivery = if this + delay > *last {
+            this + delay
+        } else {
+            *last + Duration::from_nanos(1)
+        };
+        *last = delivery;
+        delivery - this
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::Network;
+    use super::FifoNetwork;
+
+    // the inner model, it returns the scripted delays in turn
+    struct Scripted(Vec<Duration>);
+
+    impl Network<()> for Scripted {
+        fn delay(&mut self, _: Duration, _: usize, _: usize, _: usize, _: usize, _: &()) -> Duration {
+            self.0.remove(0)
+        }
+    }
+
+    #[test]
+    fn later_message_is_not_delivered_first() {
+        let ms = Duration::from_millis;
+        let mut network = FifoNetwork::new(Scripted(vec![ms(100), ms(10), ms(10), ms(100), ms(500)]));
+        // the inner model would deliver the second at 30 ms, before the first
+        assert_eq!(network.delay(ms(0), 0, 0, 0, 1, &()), ms(100));
+        assert_eq!(network.delay(ms(20), 1, 0, 0, 1, &()), ms(80) + Duration::from_nanos(1));
+        // other links keep their own order
+        assert_eq!(network.delay(ms(20), 1, 1, 1, 0, &()), ms(10));
+        // the same delivery time as the previous one is pushed too
+        assert_eq!(network.delay(ms(0), 2, 0, 0, 1, &()), ms(100) + Duration::from_nanos(2));
+        assert_eq!(network.delay(ms(200), 3, 0, 0, 1, &()), ms(500));
+    }
+}
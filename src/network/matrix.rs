@@ -0,0 +1,201 @@
+use std::{collections::BTreeMap, error::Error, fmt, time::Duration};
+use super::{Network, mix};
+
+pub struct MatrixNetwork {
+    regions: Vec<usize>,
+    latency: Vec<Vec<Duration>>,
+    jitter: Vec<Vec<Duration>>,
+    seed: u64,
+}
+
+#[derive(Debug)]
+pub enum MatrixError {
+    Syntax {
+        line: usize,
+        reason: String,
+    },
+    MissingLink {
+        from: String,
+        to: String,
+    },
+}
+
+impl fmt::Display for MatrixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatrixError::Syntax { line, reason } => write!(f, "line {}: {}", line, reason),
+            MatrixError::MissingLink { from, to } => write!(f, "no latency for {} -> {}", from, to),
+        }
+    }
+}
+
+impl Error for MatrixError {}
+
+impl MatrixNetwork {
+    // `regions[node_id]` indexes the rows and columns of the matrices
+    pub fn new(
+        regions: Vec<usize>,
+        latency: Vec<Vec<Duration>>,
+        jitter: Vec<Vec<Duration>>,
+        seed: u64,
+    ) -> Self {
+        let size = latency.len();
+        assert!(latency.iter().chain(jitter.iter()).all(|row| row.len() == size));
+        assert_eq!(jitter.len(), size);
+        assert!(regions.iter().all(|&region| region < size));
+        MatrixNetwork {
+            regions,
+            latency,
+            jitter,
+            seed,
+        }
+    }
+
+    // every line of the csv is `from,to,latency_ms,jitter_ms`, a link given
+    // in one direction only is assumed to be symmetric, `#` starts a comment
+    pub fn from_csv(node_regions: &[&str], csv: &str, seed: u64) -> Result<Self, MatrixError> {
+        let mut names = BTreeMap::new();
+        for &name in node_regions {
+            let index = names.len();
+            names.entry(name).or_insert(index);
+        }
+
+        let mut links = BTreeMap::new();
+        for (number, line) in csv.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let syntax = |reason: &str| MatrixError::Syntax {
+                line: number + 1,
+                reason: reason.to_string(),
+            };
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            if fields.len() != 4 {
+                return Err(syntax("expected `from,to,latency_ms,jitter_ms`"));
+            }
+            let millis = |s: &str| {
+                s.parse::<f64>()
+                    .ok()
+                    .filter(|ms| ms.is_finite() && *ms >= 0.0)
+                    .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+                    .ok_or_else(|| syntax("bad number of milliseconds"))
+            };
+            let link = (millis(fields[2])?, millis(fields[3])?);
+            links.insert((fields[0].to_string(), fields[1].to_string()), link);
+        }
+
+        let size = names.len();
+        let mut latency = vec![vec![Duration::default(); size]; size];
+        let mut jitter = vec![vec![Duration::default(); size]; size];
+        for (&from, &i) in &names {
+            for (&to, &j) in &names {
+                let forward = (from.to_string(), to.to_string());
+                let backward = (to.to_string(), from.to_string());
+                let &(l, d) = links
+                    .get(&forward)
+                    .or_else(|| links.get(&backward))
+                    .ok_or_else(|| MatrixError::MissingLink {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                    })?;
+                latency[i][j] = l;
+                jitter[i][j] = d;
+            }
+        }
+
+        let regions = node_regions.iter().map(|name| names[name]).collect();
+        Ok(MatrixNetwork::new(regions, latency, jitter, seed))
+    }
+}
+
+impl<M> Network<M> for MatrixNetwork {
+    fn delay(
+        &mut self,
+        this: Duration,
+        iteration: usize,
+        effect_index: usize,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &M,
+    ) -> Duration {
+        let _ = (this, message);
+        let from = self.regions[sender_node_id];
+        let to = self.regions[receiver_node_id];
+        let latency = self.latency[from][to].as_nanos() as u64;
+        let jitter = self.jitter[from][to].as_nanos() as u64;
+        let r = mix(&[
+            self.seed,
+            iteration as u64,
+            effect_index as u64,
+            sender_node_id as u64,
+            receiver_node_id as u64,
+        ]);
+        // uniform in `latency ± jitter`
+        let shift = r % (2 * jitter + 1);
+        Duration::from_nanos((latency + shift).saturating_sub(jitter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::Network;
+    use super::{MatrixNetwork, MatrixError};
+
+    const CSV: &str = "
+# from,to,latency_ms,jitter_ms
+eu,eu,1,0
+us,us,2,0
+eu,us,80,0 # one direction is enough
+";
+
+    fn delay(network: &mut MatrixNetwork, iteration: usize, from: usize, to: usize) -> Duration {
+        network.delay(Duration::from_secs(1), iteration, 0, from, to, &())
+    }
+
+    #[test]
+    fn missing_direction_is_symmetric() {
+        let mut network = MatrixNetwork::from_csv(&["eu", "us", "eu"], CSV, 0).unwrap();
+        assert_eq!(delay(&mut network, 0, 0, 1), Duration::from_millis(80));
+        assert_eq!(delay(&mut network, 0, 1, 0), Duration::from_millis(80));
+        assert_eq!(delay(&mut network, 0, 0, 2), Duration::from_millis(1));
+        assert_eq!(delay(&mut network, 0, 1, 1), Duration::from_millis(2));
+    }
+
+    #[test]
+    fn missing_link() {
+        let csv = "eu,eu,1,0\neu,us,80,0\n";
+        match MatrixNetwork::from_csv(&["eu", "us"], csv, 0) {
+            Err(MatrixError::MissingLink { from, to }) => assert_eq!((from.as_str(), to.as_str()), ("us", "us")),
+            _ => panic!("us -> us is not given"),
+        }
+    }
+
+    #[test]
+    fn bad_lines() {
+        for &(csv, line) in &[
+            ("eu,eu,1,0\n\neu,us,-5,0", 3),
+            ("eu,eu,one,0", 1),
+            ("eu,eu,1,inf", 1),
+            ("# comment\neu,eu,1", 2),
+        ] {
+            match MatrixNetwork::from_csv(&["eu"], csv, 0) {
+                Err(MatrixError::Syntax { line: found, .. }) => assert_eq!(found, line, "{}", csv),
+                _ => panic!("{} must not parse", csv),
+            }
+        }
+    }
+
+    #[test]
+    fn jitter_is_deterministic_and_bounded() {
+        let csv = "a,a,0,0\nb,b,0,0\na,b,50,10";
+        let mut network = MatrixNetwork::from_csv(&["a", "b"], csv, 7).unwrap();
+        let mut again = MatrixNetwork::from_csv(&["a", "b"], csv, 7).unwrap();
+        let delays = (0..1000).map(|i| delay(&mut network, i, 0, 1)).collect::<Vec<_>>();
+        assert_eq!(delays, (0..1000).map(|i| delay(&mut again, i, 0, 1)).collect::<Vec<_>>());
+        assert!(delays.iter().all(|&d| d >= Duration::from_millis(40) && d <= Duration::from_millis(60)));
+        assert!(delays.iter().any(|&d| d < Duration::from_millis(45)));
+        assert!(delays.iter().any(|&d| d > Duration::from_millis(55)));
+    }
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+
+mod matrix;
+pub use self::matrix::{MatrixNetwork, MatrixError};
+
+mod bandwidth;
+pub use self::bandwidth::BandwidthNetwork;
+
+mod fifo;
+pub use self::fifo::FifoNetwork;
+
+mod adversary;
+pub use self::adversary::{AdversarialNetwork, Delivery, slow_senders, split_late};
+
+pub trait Network<M> {
+    // how long the message is in flight, it is delivered at `this + delay`
+    #[allow(clippy::too_many_arguments)]
+    fn delay(
+        &mut self,
+        this: Duration,
+        iteration: usize,
+        effect_index: usize,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &M,
+    ) -> Duration;
+}
+
+impl<M, D> Network<M> for Box<D>
+where
+    D: Network<M> + ?Sized,
+{
+    fn delay(
+        &mut self,
+        this: Duration,
+        iteration: usize,
+        effect_index: usize,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &M,
+    ) -> Duration {
+        (**self).delay(this, iteration, effect_index, sender_node_id, receiver_node_id, message)
+    }
+}
+
+pub trait MessageSize {
+    // number of bytes the message occupies on the wire
+    fn size(&self) -> usize;
+}
+
+impl MessageSize for usize {
+    fn size(&self) -> usize {
+        std::mem::size_of::<u64>()
+    }
+}
+
+// stateless pseudo random number, the same inputs always give the same delay
+pub fn mix(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |acc, &v| {
+        let mut z = (acc ^ v).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    })
+}
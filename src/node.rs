@@ -1,23 +1,33 @@
 use std::time::Duration;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum NodeEvent<M> {
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeEvent<M, I = ()> {
+    Start {
+        node_id: usize,
+        node_number: usize,
+    },
     WakeUp,
     MessageReceived(M),
+    // comes from outside of the system, e.g. a client request
+    Input(I),
 }
 
 pub enum Effect<M> {
     ShutDown,
     SetWakeUpTime(Duration),
     Broadcast(M),
+    // to a single node, directly even if there is an overlay
+    Send(usize, M),
 }
 
 pub trait Node {
     type Message: Clone;
+    type Input;
 
     fn handle_event(
         &mut self,
         time: Duration,
-        event: &NodeEvent<Self::Message>,
+        event: &NodeEvent<Self::Message, Self::Input>,
     ) -> Vec<Effect<Self::Message>>;
 }
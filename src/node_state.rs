@@ -2,10 +2,13 @@ use std::{time::Duration, fmt, cmp::Ordering};
 use super::{
     node::{Node, NodeEvent, Effect},
     event::EventItem,
+    clock::Clock,
 };
 
 pub struct NodeState<N> {
     node: Option<N>,
+    started: bool,
+    clock: Clock,
     wake_up_time: Option<Duration>,
 }
 
@@ -13,16 +16,43 @@ impl<N> NodeState<N> {
     pub fn new(node: N) -> Self {
         NodeState {
             node: Some(node),
-            wake_up_time: None,
+            started: false,
+            clock: Clock::perfect(),
+            // the first wake up is delivered as `NodeEvent::Start`
+            wake_up_time: Some(Duration::from_secs(0)),
         }
     }
 
+    pub fn node(&self) -> Option<&N> {
+        self.node.as_ref()
+    }
+
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    pub fn set_start_time(&mut self, time: Duration) {
+        assert!(!self.started, "cannot change start time of a running node");
+        self.wake_up_time = Some(time);
+    }
+
     pub fn shut_down(&mut self) {
         self.node = None;
+        self.wake_up_time = None;
     }
 
-    pub fn set_wake_up_time(&mut self, time: Duration) {
-        self.wake_up_time = Some(time);
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    pub fn local_time(&self, global_time: Duration) -> Duration {
+        self.clock.local(global_time)
+    }
+
+    // the node requests the wake up by its local clock, a wake up in the past
+    // fires right away, so the time of the simulation never goes backward
+    pub fn set_wake_up_time(&mut self, now: Duration, local_time: Duration) {
+        self.wake_up_time = Some(self.clock.global(local_time).max(now));
     }
 
     pub fn clear_wake_up_time(&mut self) -> Option<Duration> {
@@ -38,12 +68,28 @@ impl<N> NodeState<N>
 where
     N: Node,
     N::Message: fmt::Debug,
+    N::Input: fmt::Debug,
 {
-    pub fn handle_event(&mut self, event: EventItem<NodeEvent<N::Message>>) -> Vec<Effect<N::Message>> {
+    pub fn take_wake_up(
+        &mut self,
+        node_id: usize,
+        node_number: usize,
+    ) -> Option<EventItem<NodeEvent<N::Message, N::Input>>> {
+        let time = self.clear_wake_up_time()?;
+        let event = if self.started {
+            NodeEvent::WakeUp
+        } else {
+            self.started = true;
+            NodeEvent::Start { node_id, node_number }
+        };
+        Some(EventItem::new(time, 0, 0, node_id, event))
+    }
+
+    pub fn handle_event(&mut self, event: EventItem<NodeEvent<N::Message, N::Input>>) -> Vec<Effect<N::Message>> {
         self.node
             .as_mut()
-            .expect(&format!("node was shutdown, cannot handle {:?}", event))
-            .handle_event(event.time(), event.event())
+            .unwrap_or_else(|| panic!("node was shutdown, cannot handle {:?}", event))
+            .handle_event(self.clock.local(event.time()), event.event())
     }
 }
 
@@ -58,7 +104,7 @@ impl<N> Eq for NodeState<N> {
 
 impl<N> PartialOrd for NodeState<N> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.wake_up_time.partial_cmp(&other.wake_up_time)
+        Some(self.cmp(other))
     }
 }
 
@@ -0,0 +1,40 @@
+use std::time::Duration;
+use super::node::{Node, NodeEvent};
+
+// passive hooks called by `Simulator::run_observed`
+pub trait Observer<N>
+where
+    N: Node,
+{
+    // `local_time` is what the clock of the node reads, the node handles
+    // the event at that time
+    fn on_event(
+        &mut self,
+        time: Duration,
+        local_time: Duration,
+        node_id: usize,
+        event: &NodeEvent<N::Message, N::Input>,
+    ) {
+        let _ = (time, local_time, node_id, event);
+    }
+
+    fn on_send(
+        &mut self,
+        time: Duration,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: &N::Message,
+    ) {
+        let _ = (time, sender_node_id, receiver_node_id, message);
+    }
+
+    fn on_step(&mut self, time: Duration, nodes: &[Option<&N>]) {
+        let _ = (time, nodes);
+    }
+}
+
+impl<N> Observer<N> for ()
+where
+    N: Node,
+{
+}
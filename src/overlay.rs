@@ -0,0 +1,183 @@
+use std::collections::BTreeSet;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Relay {
+    broadcast_id: u64,
+    hops: usize,
+}
+
+impl Relay {
+    pub fn next_hop(self) -> Self {
+        Relay {
+            broadcast_id: self.broadcast_id,
+            hops: self.hops + 1,
+        }
+    }
+}
+
+// broadcasts are flooded along the peer graph, every node delivers
+// a message to the protocol and forwards it only the first time
+pub struct Overlay {
+    peers: Vec<Vec<usize>>,
+    seen: Vec<BTreeSet<u64>>,
+    next_broadcast_id: u64,
+    hop_histogram: Vec<usize>,
+}
+
+impl Overlay {
+    pub fn from_adjacency(peers: Vec<Vec<usize>>) -> Self {
+        let node_number = peers.len();
+        assert!(peers.iter().flatten().all(|&peer| peer < node_number));
+        Overlay {
+            seen: vec![BTreeSet::new(); node_number],
+            peers,
+            next_broadcast_id: 0,
+            hop_histogram: vec![],
+        }
+    }
+
+    // random `degree`-regular graph by the pairing model: every node has
+    // `degree` points, random pairs of points that give neither a loop nor
+    // a repeated edge become edges, and it starts over if the points left
+    // cannot be paired
+    pub fn random_regular(node_number: usize, degree: usize, seed: u64) -> Self {
+        assert!(
+            degree < node_number && node_number * degree % 2 == 0,
+            "degree must be less than node number and their product even",
+        );
+        let mut rng = StdRng::seed_from_u64(seed);
+        let adjacency = loop {
+            if let Some(adjacency) = pair_points(node_number, degree, &mut rng) {
+                break adjacency;
+            }
+        };
+        Overlay::from_adjacency(adjacency.into_iter().map(|s| s.into_iter().collect()).collect())
+    }
+
+    // Watts-Strogatz graph, every lattice edge is rewired to a random
+    // node with probability `rewire_probability`
+    pub fn small_world(
+        node_number: usize,
+        degree: usize,
+        rewire_probability: f64,
+        seed: u64,
+    ) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut adjacency = vec![BTreeSet::new(); node_number];
+        for (a, b) in ring_lattice(node_number, degree) {
+            adjacency[a].insert(b);
+            adjacency[b].insert(a);
+        }
+        for (a, b) in ring_lattice(node_number, degree) {
+            if !rng.gen_bool(rewire_probability) || adjacency[a].len() + 1 >= node_number {
+                continue;
+            }
+            let c = loop {
+                let c = rng.gen_range(0..node_number);
+                if c != a && !adjacency[a].contains(&c) {
+                    break c;
+                }
+            };
+            adjacency[a].remove(&b);
+            adjacency[b].remove(&a);
+            adjacency[a].insert(c);
+            adjacency[c].insert(a);
+        }
+        Overlay::from_adjacency(adjacency.into_iter().map(|s| s.into_iter().collect()).collect())
+    }
+
+    pub fn node_number(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn peers(&self, node_id: usize) -> &[usize] {
+        &self.peers[node_id]
+    }
+
+    // `hop_histogram()[h]` is the number of deliveries after exactly `h` hops
+    pub fn hop_histogram(&self) -> &[usize] {
+        &self.hop_histogram
+    }
+
+    pub fn originate(&mut self, node_id: usize) -> Relay {
+        let broadcast_id = self.next_broadcast_id;
+        self.next_broadcast_id += 1;
+        self.seen[node_id].insert(broadcast_id);
+        Relay {
+            broadcast_id,
+            hops: 1,
+        }
+    }
+
+    // returns false if the node has already seen the broadcast
+    pub fn deliver(&mut self, node_id: usize, relay: Relay) -> bool {
+        if !self.seen[node_id].insert(relay.broadcast_id) {
+            return false;
+        }
+        if self.hop_histogram.len() <= relay.hops {
+            self.hop_histogram.resize(relay.hops + 1, 0);
+        }
+        self.hop_histogram[relay.hops] += 1;
+        true
+    }
+}
+
+fn ring_lattice(node_number: usize, degree: usize) -> impl Iterator<Item = (usize, usize)> {
+    assert!(degree & 1 == 0 && degree < node_number, "degree must be even and less than node number");
+    (0..node_number)
+        .flat_map(move |a| (1..=(degree / 2)).map(move |j| (a, (a + j) % node_number)))
+}
+
+fn pair_points(node_number: usize, degree: usize, rng: &mut StdRng) -> Option<Vec<BTreeSet<usize>>> {
+    let mut points = (0..node_number)
+        .flat_map(|a| std::iter::repeat(a).take(degree))
+        .collect::<Vec<_>>();
+    let mut adjacency = vec![BTreeSet::new(); node_number];
+    let mut failures = 0;
+    while !points.is_empty() {
+        let i = rng.gen_range(0..points.len());
+        let j = rng.gen_range(0..points.len());
+        let (a, b) = (points[i], points[j]);
+        if a == b || adjacency[a].contains(&b) {
+            failures += 1;
+            // only look for a pair that fits once random picks keep failing
+            if failures > points.len() {
+                let stuck = points
+                    .iter()
+                    .all(|&a| points.iter().all(|&b| a == b || adjacency[a].contains(&b)));
+                if stuck {
+                    return None;
+                }
+                failures = 0;
+            }
+            continue;
+        }
+        adjacency[a].insert(b);
+        adjacency[b].insert(a);
+        points.swap_remove(i.max(j));
+        points.swap_remove(i.min(j));
+        failures = 0;
+    }
+    Some(adjacency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Overlay;
+
+    #[test]
+    fn random_regular_is_simple_and_regular() {
+        for &(node_number, degree) in &[(10, 3), (50, 4), (101, 8), (20, 19)] {
+            for seed in 0..10 {
+                let overlay = Overlay::random_regular(node_number, degree, seed);
+                for a in 0..node_number {
+                    let peers = overlay.peers(a);
+                    assert_eq!(peers.len(), degree);
+                    assert!(!peers.contains(&a));
+                    assert!(peers.iter().all(|&b| overlay.peers(b).contains(&a)));
+                }
+            }
+        }
+    }
+}
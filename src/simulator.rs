@@ -1,10 +1,15 @@
 
-use std::{collections::BinaryHeap, fmt};
+use std::{collections::BinaryHeap, time::Duration, fmt};
 use super::{
     node::{Node, NodeEvent, Effect},
     event::EventItem,
     node_state::NodeState,
+    clock::Clock,
     network::Network,
+    overlay::{Overlay, Relay},
+    invariant::{Invariant, Violation, Decisions},
+    observer::Observer,
+    fault::{FaultSchedule, Fault, Faults, Adversary},
 };
 
 pub struct Simulator<N, D>
@@ -12,86 +17,385 @@ where
     N: Node,
 {
     node_pool: Vec<NodeState<N>>,
-    event_heap: BinaryHeap<EventItem<NodeEvent<N::Message>>>,
+    event_heap: BinaryHeap<EventItem<NodeEvent<N::Message, N::Input>>>,
     network: D,
+    overlay: Option<Overlay>,
+    faults: Option<Faults<N>>,
+    iteration: usize,
+    inputs: usize,
+    // the time of the last handled event
+    time: Duration,
 }
 
 impl<N, D> Simulator<N, D>
 where
     N: Node,
     N::Message: fmt::Debug,
-    D: Network,
+    N::Input: fmt::Debug,
+    D: Network<N::Message>,
 {
     pub fn new(nodes: impl Iterator<Item = N>, network: D) -> Self {
         Simulator {
             node_pool: nodes.map(NodeState::new).collect(),
             event_heap: BinaryHeap::new(),
             network,
+            overlay: None,
+            faults: None,
+            iteration: 0,
+            inputs: 0,
+            time: Duration::from_secs(0),
         }
     }
 
-    fn pop_valid_event(&mut self) -> Option<EventItem<NodeEvent<N::Message>>> {
+    pub fn with_overlay(mut self, overlay: Overlay) -> Self {
+        assert_eq!(overlay.node_number(), self.node_pool.len());
+        self.overlay = Some(overlay);
+        self
+    }
+
+    pub fn overlay(&self) -> Option<&Overlay> {
+        self.overlay.as_ref()
+    }
+
+    pub fn with_faults(mut self, schedule: FaultSchedule) -> Self
+    where
+        N: Decisions,
+    {
+        self.faults = Some(Faults::new(schedule, self.node_pool.len()));
+        self
+    }
+
+    // byzantine nodes are silent unless an adversary is given
+    pub fn with_adversary<A>(mut self, adversary: A) -> Self
+    where
+        A: Adversary<N::Message> + 'static,
+    {
+        self.faults
+            .as_mut()
+            .expect("the adversary requires a fault schedule")
+            .set_adversary(Box::new(adversary));
+        self
+    }
+
+    pub fn with_start_times(mut self, start_times: impl IntoIterator<Item = Duration>) -> Self {
+        for (state, time) in self.node_pool.iter_mut().zip(start_times) {
+            state.set_start_time(time);
+        }
+        self
+    }
+
+    pub fn with_clocks(mut self, clocks: impl IntoIterator<Item = Clock>) -> Self {
+        for (state, clock) in self.node_pool.iter_mut().zip(clocks) {
+            state.set_clock(clock);
+        }
+        self
+    }
+
+    fn pop_valid_event(&mut self) -> Option<EventItem<NodeEvent<N::Message, N::Input>>> {
+        let node_number = self.node_pool.len();
         let next_event_time = self.event_heap.peek().map(|e| e.time());
         let min_wake_up = self.node_pool
             .iter_mut()
-            .filter(|s| s.wake_up_time().is_some())
             .enumerate()
-            .min_by(|(_, a), (_, b)| a.cmp(&b));
+            .filter(|(_, s)| s.wake_up_time().is_some())
+            .min_by(|(_, a), (_, b)| a.cmp(b));
 
         match (next_event_time, min_wake_up) {
             (None, None) => None,
             (Some(_), None) => self.event_heap.pop(),
-            (None, Some((node_id, state))) => {
-                let time = state.clear_wake_up_time().unwrap();
-                Some(EventItem::new(time, 0, 0, node_id, NodeEvent::WakeUp))
-            },
+            (None, Some((node_id, state))) => state.take_wake_up(node_id, node_number),
             (Some(event_time), Some((node_id, state))) => {
                 if event_time < state.wake_up_time().unwrap() {
                     self.event_heap.pop()
                 } else {
-                    let time = state.clear_wake_up_time().unwrap();
-                    Some(EventItem::new(time, 0, 0, node_id, NodeEvent::WakeUp))
+                    state.take_wake_up(node_id, node_number)
                 }
             },
         }
     }
 
-    fn handle_event(&mut self, event: EventItem<NodeEvent<N::Message>>, cnt: usize) {
-        let node_number = self.node_pool.len();
+    fn handle_event<O>(&mut self, event: EventItem<NodeEvent<N::Message, N::Input>>, cnt: usize, observer: &mut O)
+    where
+        O: Observer<N>,
+    {
         let node_id = event.node_id();
         let this_time = event.time();
+        let relay = event.relay();
         let state = self.node_pool
             .get_mut(node_id)
-            .expect(&format!("event {:?} for node that doesn't exist", event));
+            .unwrap_or_else(|| panic!("event {:?} for node that doesn't exist", event));
+        if !state.started() || state.node().is_none() {
+            // the node has not joined yet or has crashed, so the message is lost
+            return;
+        }
+        if let Some(relay) = relay {
+            let overlay = self.overlay.as_mut().expect("relayed message without overlay");
+            if !overlay.deliver(node_id, relay) {
+                return;
+            }
+        }
+        observer.on_event(this_time, state.local_time(this_time), node_id, event.event());
+        let forward = match (relay, event.event()) {
+            (Some(relay), NodeEvent::MessageReceived(message)) => Some((relay, message.clone())),
+            _ => None,
+        };
         let effects = state.handle_event(event);
+        let effects = match self.faults.as_mut() {
+            Some(faults) => faults.corrupt(this_time, node_id, effects),
+            None => effects,
+        };
 
+        let mut broadcasts = vec![];
+        let mut sends = vec![];
+        let effects_number = effects.len();
         for (effect_index, effect) in effects.into_iter().enumerate() {
             match effect {
                 Effect::ShutDown => state.shut_down(),
-                Effect::SetWakeUpTime(time) => state.set_wake_up_time(time),
-                Effect::Broadcast(message) => {
-                    for i in 0..node_number {
-                        let event = NodeEvent::MessageReceived(message.clone());
+                Effect::SetWakeUpTime(time) => state.set_wake_up_time(this_time, time),
+                Effect::Broadcast(message) => broadcasts.push((effect_index, message)),
+                Effect::Send(receiver_node_id, message) => sends.push((effect_index, receiver_node_id, message)),
+            }
+        }
+
+        if let Some((relay, message)) = forward {
+            let e = effects_number;
+            self.relay(this_time, cnt, e, node_id, relay.next_hop(), message, observer);
+        }
+        for (e, message) in broadcasts {
+            match self.overlay.as_mut() {
+                None => {
+                    for i in 0..self.node_pool.len() {
                         if i != node_id {
-                            let e = effect_index;
-                            let new_time = self.network.delay(this_time, cnt, e, node_id, i);
-                            let item = EventItem::new(new_time, cnt, e, i, event);
-                            self.event_heap.push(item);
+                            let m = message.clone();
+                            self.send(this_time, cnt, e, node_id, i, m, None, observer);
                         }
                     }
+                },
+                Some(overlay) => {
+                    let relay = overlay.originate(node_id);
+                    self.relay(this_time, cnt, e, node_id, relay, message, observer);
+                },
+            }
+        }
+        for (e, receiver_node_id, message) in sends {
+            assert!(receiver_node_id < self.node_pool.len(), "message for node {} that doesn't exist", receiver_node_id);
+            self.send(this_time, cnt, e, node_id, receiver_node_id, message, None, observer);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn relay<O>(
+        &mut self,
+        this_time: Duration,
+        cnt: usize,
+        effect_index: usize,
+        node_id: usize,
+        relay: Relay,
+        message: N::Message,
+        observer: &mut O,
+    )
+    where
+        O: Observer<N>,
+    {
+        let peers = self.overlay
+            .as_ref()
+            .map(|overlay| overlay.peers(node_id).to_vec())
+            .unwrap_or_default();
+        for peer in peers {
+            if peer != node_id {
+                let m = message.clone();
+                self.send(this_time, cnt, effect_index, node_id, peer, m, Some(relay), observer);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn send<O>(
+        &mut self,
+        this_time: Duration,
+        cnt: usize,
+        effect_index: usize,
+        sender_node_id: usize,
+        receiver_node_id: usize,
+        message: N::Message,
+        relay: Option<Relay>,
+        observer: &mut O,
+    )
+    where
+        O: Observer<N>,
+    {
+        observer.on_send(this_time, sender_node_id, receiver_node_id, &message);
+        if let Some(faults) = &self.faults {
+            if faults.is_partitioned(this_time, sender_node_id, receiver_node_id) {
+                return;
+            }
+        }
+        let delay = self.network.delay(
+            this_time,
+            cnt,
+            effect_index,
+            sender_node_id,
+            receiver_node_id,
+            &message,
+        );
+        let event = NodeEvent::MessageReceived(message);
+        let item = EventItem::new(this_time + delay, cnt, effect_index, receiver_node_id, event)
+            .with_relay(relay);
+        self.event_heap.push(item);
+    }
+
+    // the input is lost if the node has not started by `time` or has crashed,
+    // `time` must not be before the event the simulator handled last
+    pub fn inject(&mut self, time: Duration, node_id: usize, input: N::Input) {
+        assert!(node_id < self.node_pool.len(), "input for node {} that doesn't exist", node_id);
+        assert!(time >= self.time, "input at {:?} is in the past, it is {:?}", time, self.time);
+        // inputs are ordered among themselves by the order of injection
+        let item = EventItem::new(time, 0, self.inputs, node_id, NodeEvent::Input(input));
+        self.inputs += 1;
+        self.event_heap.push(item);
+    }
+
+    pub fn shut_down(&mut self, node_id: usize) {
+        self.node_pool[node_id].shut_down();
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = Option<&N>> {
+        self.node_pool.iter().map(NodeState::node)
+    }
+
+    fn step<O>(&mut self, observer: &mut O) -> Option<Duration>
+    where
+        O: Observer<N>,
+    {
+        let event = self.pop_valid_event()?;
+        let time = event.time();
+        self.time = time;
+        if let Some(faults) = self.faults.as_mut() {
+            for fault in faults.due(time) {
+                if let Fault::Crash { node_id, .. } = fault {
+                    self.node_pool[node_id].shut_down();
                 }
             }
         }
+        self.handle_event(event, self.iteration, observer);
+        if let Some(faults) = self.faults.as_mut() {
+            let nodes = self.node_pool.iter().map(NodeState::node).collect::<Vec<_>>();
+            faults.check_levels(&nodes);
+        }
+        self.iteration += 1;
+        Some(time)
+    }
+
+    pub fn run(&mut self, iterations_number: usize) {
+        self.run_observed(iterations_number, &mut ())
+    }
+
+    pub fn run_observed<O>(&mut self, iterations_number: usize, observer: &mut O)
+    where
+        O: Observer<N>,
+    {
+        self.run_until(iterations_number, observer, |_, _| false)
     }
 
-    pub fn run(mut self, iterations_number: usize) {
+    // stops early once `stop` returns true, it is called after every step
+    pub fn run_until<O, F>(&mut self, iterations_number: usize, observer: &mut O, mut stop: F)
+    where
+        O: Observer<N>,
+        F: FnMut(Duration, &[Option<&N>]) -> bool,
+    {
         let mut cnt = 0;
-        while let Some(event) = self.pop_valid_event() {
-            self.handle_event(event, cnt);
+        while let Some(time) = self.step(observer) {
+            let nodes = self.nodes().collect::<Vec<_>>();
+            observer.on_step(time, &nodes);
             cnt += 1;
-            if cnt == iterations_number {
+            if stop(time, &nodes) || cnt == iterations_number {
                 break;
             }
         }
     }
+
+    // stops at the first violation, the observer sees every step up to it
+    pub fn run_checked<O, I>(
+        &mut self,
+        iterations_number: usize,
+        observer: &mut O,
+        invariant: &mut I,
+    ) -> Result<(), Violation>
+    where
+        O: Observer<N>,
+        I: Invariant<N>,
+    {
+        let mut result = Ok(());
+        self.run_until(iterations_number, observer, |time, nodes| {
+            result = invariant.check(time, nodes);
+            result.is_err()
+        });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use crate::{Node, NodeEvent, Effect, Observer, CaseNetwork, DelayModel};
+    use super::Simulator;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    struct Idle;
+
+    impl Node for Idle {
+        type Message = ();
+        type Input = u32;
+
+        fn handle_event(&mut self, _: Duration, _: &NodeEvent<(), u32>) -> Vec<Effect<()>> {
+            vec![]
+        }
+    }
+
+    // `(time, node_id, input)` of every input handled
+    struct Inputs(Vec<(Duration, usize, u32)>);
+
+    impl Observer<Idle> for Inputs {
+        fn on_event(&mut self, time: Duration, _: Duration, node_id: usize, event: &NodeEvent<(), u32>) {
+            if let NodeEvent::Input(input) = event {
+                self.0.push((time, node_id, *input));
+            }
+        }
+    }
+
+    fn simulator() -> Simulator<Idle, CaseNetwork> {
+        let network = CaseNetwork::new(DelayModel::Constant(SECOND), 0);
+        Simulator::new((0..3).map(|_| Idle), network).with_start_times(vec![Duration::from_secs(0), Duration::from_secs(0), SECOND * 5])
+    }
+
+    #[test]
+    fn input_reaches_the_node() {
+        let mut simulator = simulator();
+        simulator.inject(SECOND * 2, 1, 7);
+        simulator.inject(SECOND, 0, 3);
+        // the same time, handled in the order of injection
+        simulator.inject(SECOND * 2, 0, 8);
+        let mut inputs = Inputs(vec![]);
+        simulator.run_observed(100, &mut inputs);
+        assert_eq!(inputs.0, [(SECOND, 0, 3), (SECOND * 2, 1, 7), (SECOND * 2, 0, 8)]);
+    }
+
+    #[test]
+    fn input_before_start_is_lost() {
+        let mut simulator = simulator();
+        simulator.inject(SECOND, 2, 1);
+        simulator.inject(SECOND * 6, 2, 2);
+        let mut inputs = Inputs(vec![]);
+        simulator.run_observed(100, &mut inputs);
+        assert_eq!(inputs.0, [(SECOND * 6, 2, 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "in the past")]
+    fn input_in_the_past() {
+        let mut simulator = simulator();
+        simulator.run(100);
+        simulator.inject(SECOND, 0, 1);
+    }
 }
@@ -0,0 +1,8 @@
+use std::time::Duration;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+pub fn random_start_times(seed: u64, max_skew: Duration) -> impl Iterator<Item = Duration> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let max_skew = max_skew.as_nanos() as u64;
+    std::iter::repeat_with(move || Duration::from_nanos(rng.gen_range(0..=max_skew)))
+}
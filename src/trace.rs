@@ -0,0 +1,124 @@
+use std::time::Duration;
+use super::{
+    node::{Node, NodeEvent, Effect},
+    explorer::Step,
+    observer::Observer,
+};
+
+// every event the simulator delivers, in order and by the clock of the node
+// that handles it; `replay` feeds it to the same initial nodes and gets
+// their states back without the network
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trace<M, I = ()> {
+    steps: Vec<Step<M, I>>,
+}
+
+impl<M, I> Default for Trace<M, I> {
+    fn default() -> Self {
+        Trace { steps: vec![] }
+    }
+}
+
+impl<M, I> Trace<M, I> {
+    pub fn new() -> Self {
+        Trace::default()
+    }
+
+    pub fn steps(&self) -> &[Step<M, I>] {
+        &self.steps
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl<M, I> From<Vec<Step<M, I>>> for Trace<M, I> {
+    fn from(steps: Vec<Step<M, I>>) -> Self {
+        Trace { steps }
+    }
+}
+
+impl<N> Observer<N> for Trace<N::Message, N::Input>
+where
+    N: Node,
+    N::Input: Clone,
+{
+    fn on_event(
+        &mut self,
+        time: Duration,
+        local_time: Duration,
+        node_id: usize,
+        event: &NodeEvent<N::Message, N::Input>,
+    ) {
+        let _ = time;
+        self.steps.push(Step {
+            time: local_time,
+            node_id,
+            event: event.clone(),
+        });
+    }
+}
+
+// the nodes after handling the trace, the effects are dropped except that a
+// node which shuts itself down becomes `None`
+pub fn replay<N>(nodes: impl Iterator<Item = N>, trace: &Trace<N::Message, N::Input>) -> Vec<Option<N>>
+where
+    N: Node,
+{
+    let mut nodes = nodes.map(Some).collect::<Vec<_>>();
+    for step in &trace.steps {
+        let node = match nodes.get_mut(step.node_id).and_then(Option::as_mut) {
+            Some(node) => node,
+            None => continue,
+        };
+        let effects = node.handle_event(step.time, &step.event);
+        if effects.iter().any(|effect| matches!(effect, Effect::ShutDown)) {
+            nodes[step.node_id] = None;
+        }
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::{Hash, Hasher},
+        time::Duration,
+    };
+    use crate::{Simulator, CaseNetwork, DelayModel, StreamletNode, StreamletConfig, random_clocks};
+    use super::{Trace, replay};
+
+    fn hash(node: Option<&StreamletNode>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        node.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // the epochs follow the clocks, so the replay only matches if the trace
+    // has the time each node saw
+    #[test]
+    fn replay_restores_the_recorded_states() {
+        let epoch_duration = Duration::from_secs(1);
+        let nodes = || StreamletConfig::new(4, epoch_duration).map(StreamletNode::new);
+        let delay = DelayModel::Uniform {
+            min: Duration::from_millis(1),
+            max: Duration::from_millis(100),
+        };
+        let mut simulator = Simulator::new(nodes(), CaseNetwork::new(delay, 0))
+            .with_clocks(random_clocks(0, Duration::from_millis(50), 0.001));
+        let mut trace = Trace::new();
+        simulator.run_until(100_000, &mut trace, |time, _| time >= epoch_duration * 10);
+
+        let replayed = replay(nodes(), &trace);
+        assert!(simulator.nodes().all(|node| node.map_or(false, |node| !node.finalized().is_empty())));
+        let recorded = simulator.nodes().map(hash).collect::<Vec<_>>();
+        assert_eq!(recorded, replayed.iter().map(Option::as_ref).map(hash).collect::<Vec<_>>());
+    }
+}
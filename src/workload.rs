@@ -0,0 +1,84 @@
+use std::time::Duration;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arrival {
+    pub time: Duration,
+    pub node_id: usize,
+}
+
+// `rate` arrivals per second at a fixed interval, each to a random node
+pub fn steady(seed: u64, rate: f64, node_number: usize) -> impl Iterator<Item = Arrival> {
+    assert!(rate > 0.0 && node_number > 0);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let interval = Duration::from_secs_f64(1.0 / rate);
+    (0u32..).map(move |i| Arrival {
+        time: interval * i,
+        node_id: rng.gen_range(0..node_number),
+    })
+}
+
+// bursts of `burst` length separated by `idle` periods of silence, during a
+// burst the arrivals form a poisson process with `rate` arrivals per second
+pub fn bursty(
+    seed: u64,
+    rate: f64,
+    burst: Duration,
+    idle: Duration,
+    node_number: usize,
+) -> impl Iterator<Item = Arrival> {
+    assert!(rate > 0.0 && node_number > 0 && burst > Duration::default());
+    let mut rng = StdRng::seed_from_u64(seed);
+    // time spent inside bursts only, mapped to real time when emitted
+    let mut active = Duration::default();
+    std::iter::repeat_with(move || {
+        let u: f64 = rng.gen();
+        active += Duration::from_secs_f64(-(1.0 - u).ln() / rate);
+        let bursts = (active.as_nanos() / burst.as_nanos()) as u32;
+        Arrival {
+            time: active + idle * bursts,
+            node_id: rng.gen_range(0..node_number),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{Arrival, steady, bursty};
+
+    #[test]
+    fn steady_arrivals() {
+        let arrivals = steady(1, 4.0, 5).take(400).collect::<Vec<_>>();
+        assert_eq!(arrivals, steady(1, 4.0, 5).take(400).collect::<Vec<_>>());
+        assert_ne!(arrivals, steady(2, 4.0, 5).take(400).collect::<Vec<_>>());
+        for (i, arrival) in arrivals.iter().enumerate() {
+            assert_eq!(arrival.time, Duration::from_millis(250) * i as u32);
+        }
+        // every node gets some
+        for node_id in 0..5 {
+            assert!(arrivals.iter().any(|arrival| arrival.node_id == node_id));
+        }
+        assert!(arrivals.iter().all(|arrival| arrival.node_id < 5));
+    }
+
+    #[test]
+    fn bursty_arrivals() {
+        let (burst, idle) = (Duration::from_millis(100), Duration::from_millis(900));
+        let arrivals = |seed| {
+            bursty(seed, 1000.0, burst, idle, 5)
+                .take_while(|arrival: &Arrival| arrival.time < Duration::from_secs(100))
+                .collect::<Vec<_>>()
+        };
+        let a = arrivals(1);
+        assert_eq!(a, arrivals(1));
+        assert_ne!(a, arrivals(2));
+        assert!(a.windows(2).all(|w| w[0].time <= w[1].time));
+        // nothing arrives while idle
+        let period = (burst + idle).as_nanos();
+        assert!(a.iter().all(|arrival| arrival.time.as_nanos() % period < burst.as_nanos()));
+        // 100 bursts of 100 ms at 1000 per second
+        assert!((9_500..10_500).contains(&a.len()), "{} arrivals", a.len());
+        assert!(a.iter().all(|arrival| arrival.node_id < 5));
+    }
+}